@@ -0,0 +1,27 @@
+use criterion::{criterion_group, criterion_main, Criterion, SamplingMode};
+
+use dsrs::CpcSketch;
+
+fn bench_par(c: &mut Criterion) {
+    let mut group = c.benchmark_group("par-from-u64");
+    group.sampling_mode(SamplingMode::Flat);
+    group.sample_size(10);
+    let values: Vec<u64> = (0..10 * 1000 * 1000).collect();
+    group.bench_function("dsrs::CpcSketch::update_u64", |b| {
+        b.iter(|| {
+            let mut sketch = CpcSketch::new();
+            for &value in &values {
+                sketch.update_u64(value);
+            }
+            sketch.estimate()
+        })
+    });
+    let shards = std::thread::available_parallelism().map_or(1, |n| n.get());
+    group.bench_function("dsrs::CpcSketch::par_from_u64", |b| {
+        b.iter(|| CpcSketch::par_from_u64(&values, shards).estimate())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_par);
+criterion_main!(benches);
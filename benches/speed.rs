@@ -9,6 +9,8 @@ use criterion::{
 use hyperloglogplus::HyperLogLog;
 use ordered_float::NotNan;
 
+use dsrs::counters::KeyedCounter;
+use dsrs::stream_reducer::LineReducer;
 use dsrs::CpcSketch;
 
 struct TrialTracker {
@@ -18,13 +20,13 @@ struct TrialTracker {
 impl TrialTracker {
     fn observe(&self, name: &str, expected: u64, actual: f64) {
         let mut tracker = self.tracker.lock().unwrap();
-        let tracker = tracker.entry(expected).or_insert_with(HashMap::new);
+        let tracker = tracker.entry(expected).or_default();
         let entry = tracker.entry(name.to_owned()).or_insert(0.0);
         let relerr = (expected as f64 - actual) / (expected as f64);
         *entry = entry.max(relerr.abs());
     }
 
-    fn to_map(self) -> HashMap<u64, HashMap<String, f64>> {
+    fn into_map(self) -> HashMap<u64, HashMap<String, f64>> {
         self.tracker.into_inner().unwrap()
     }
 }
@@ -119,7 +121,7 @@ fn bench_speed(c: &mut Criterion) {
     group.finish();
 
     eprintln!("relative errors");
-    for (sz, map) in tracker.to_map().into_iter() {
+    for (sz, map) in tracker.into_map().into_iter() {
         eprintln!("size: {}", sz);
         let mut v: Vec<_> = map.into_iter().collect();
         v.sort_by_key(|(name, relerr)| (NotNan::new(*relerr).ok(), name.clone()));
@@ -129,5 +131,74 @@ fn bench_speed(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, bench_speed);
+fn bench_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("short-strings");
+    group.sampling_mode(SamplingMode::Flat);
+    group.sample_size(10);
+    let keys: Vec<String> = (0..1000 * 1000).map(|i| i.to_string()).collect();
+    let keys: Vec<&[u8]> = keys.iter().map(|key| key.as_bytes()).collect();
+    group.bench_function("dsrs::CpcSketch::update", |b| {
+        b.iter(|| {
+            let mut sketch = CpcSketch::new();
+            for key in &keys {
+                sketch.update(key);
+            }
+            sketch.estimate()
+        })
+    });
+    group.bench_function("dsrs::CpcSketch::update_batch", |b| {
+        b.iter(|| {
+            let mut sketch = CpcSketch::new();
+            for batch in keys.chunks(1024) {
+                sketch.update_batch(batch);
+            }
+            sketch.estimate()
+        })
+    });
+    group.finish();
+}
+
+fn bench_keyed(c: &mut Criterion) {
+    let mut group = c.benchmark_group("keyed-lines");
+    group.sampling_mode(SamplingMode::Flat);
+    group.sample_size(10);
+    let nkeys = 100 * 1000;
+    let lines: Vec<String> = (0..1000 * 1000)
+        .map(|i| format!("{} {}", i % nkeys, i))
+        .collect();
+    let reduce = |mut counter: KeyedCounter| {
+        for line in &lines {
+            counter.read_line(line.as_bytes());
+        }
+        counter
+    };
+    group.bench_function("dsrs::counters::KeyedCounter::default", |b| {
+        b.iter(|| reduce(KeyedCounter::default()))
+    });
+    group.bench_function("dsrs::counters::KeyedCounter::with_capacity", |b| {
+        b.iter(|| reduce(KeyedCounter::with_capacity(nkeys)))
+    });
+    group.finish();
+}
+
+fn bench_estimate_bytes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialized-estimate");
+    let values: Vec<u64> = (0..1000 * 1000).collect();
+    let bytes = CpcSketch::from_u64_slice(&values).serialize_to_vec();
+    group.bench_function("dsrs::CpcSketch::deserialize", |b| {
+        b.iter(|| CpcSketch::deserialize(&bytes).estimate())
+    });
+    group.bench_function("dsrs::CpcSketch::estimate_from_bytes", |b| {
+        b.iter(|| CpcSketch::estimate_from_bytes(&bytes).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_speed,
+    bench_batch,
+    bench_keyed,
+    bench_estimate_bytes
+);
 criterion_main!(benches);
@@ -1,3 +1,4 @@
+use std::env;
 use std::path::PathBuf;
 
 fn main() {
@@ -6,6 +7,9 @@ fn main() {
     let mut bridge = cxx_build::bridge(src.join("bridge.rs"));
 
     assert!(bridge.is_flag_supported("-std=c++11").expect("supported"));
+    if env::var_os("CARGO_FEATURE_BYTE_BUFFERS").is_some() {
+        bridge.define("DSRS_BYTE_BUFFERS", None);
+    }
     bridge
         .files(&[
             datasketches.join("cpc.cpp"),
@@ -15,6 +19,5 @@ fn main() {
         .include(datasketches.join("common").join("include"))
         .flag_if_supported("-std=c++11")
         .cpp_link_stdlib("stdc++")
-        .static_flag(true)
         .compile("libdatasketches.a");
 }
@@ -1,7 +1,9 @@
 #include <cstdint>
+#ifndef DSRS_BYTE_BUFFERS
 #include <ios>
 #include <sstream>
 #include <iostream>
+#endif
 
 #include "rust/cxx.h"
 #include "cpc/include/cpc_sketch.hpp"
@@ -16,24 +18,89 @@ OpaqueCpcSketch::OpaqueCpcSketch(datasketches::cpc_sketch&& cpc):
   inner_{std::move(cpc)} {
 }
 
+#ifndef DSRS_BYTE_BUFFERS
 OpaqueCpcSketch::OpaqueCpcSketch(std::istream& is):
   inner_{datasketches::cpc_sketch::deserialize(is)} {
 }
+#endif
 
 
 double OpaqueCpcSketch::estimate() const {
   return this->inner_.get_estimate();
 }
 
+uint8_t OpaqueCpcSketch::get_lg_k() const {
+  return this->inner_.get_lg_k();
+}
+
+bool OpaqueCpcSketch::is_empty() const {
+  return this->inner_.is_empty();
+}
+
+double OpaqueCpcSketch::get_lower_bound(uint8_t kappa) const {
+  return this->inner_.get_lower_bound(kappa);
+}
+
+double OpaqueCpcSketch::get_upper_bound(uint8_t kappa) const {
+  return this->inner_.get_upper_bound(kappa);
+}
+
+size_t OpaqueCpcSketch::get_allocated_bytes() const {
+  return this->inner_.get_allocated_bytes();
+}
+
+uint8_t OpaqueCpcSketch::get_flavor() const {
+  return this->inner_.get_flavor();
+}
+
 void OpaqueCpcSketch::update(rust::Slice<const uint8_t> buf) {
   this->inner_.update(buf.data(), buf.size());
 }
 
+void OpaqueCpcSketch::update_batch(rust::Slice<const uint8_t> buf, rust::Slice<const size_t> ends) {
+  size_t start = 0;
+  for (auto end : ends) {
+    this->inner_.update(buf.data() + start, end - start);
+    start = end;
+  }
+}
+
 void OpaqueCpcSketch::update_u64(uint64_t value) {
   this->inner_.update(value);
 }
 
+void OpaqueCpcSketch::update_u64_slice(rust::Slice<const uint64_t> values) {
+  for (auto value : values) {
+    this->inner_.update(value);
+  }
+}
+
+void OpaqueCpcSketch::update_prehashed(uint64_t hash) {
+  this->inner_.update_prehashed(hash);
+}
+
+void OpaqueCpcSketch::reset() {
+  // there's no in-place reset in datasketches-cpp, but an empty sketch
+  // holds no buffers anyway
+  this->inner_ = datasketches::cpc_sketch{this->inner_.get_lg_k()};
+}
+
+std::unique_ptr<OpaqueCpcSketch> OpaqueCpcSketch::clone() const {
+  return std::unique_ptr<OpaqueCpcSketch>(new OpaqueCpcSketch{datasketches::cpc_sketch{this->inner_}});
+}
+
+std::unique_ptr<OpaqueCpcSketch> OpaqueCpcSketch::canonical() const {
+  // a union's result carries no HIP estimator state, which is the only part
+  // of a sketch that depends on the order of its updates
+  datasketches::cpc_union u{this->inner_.get_lg_k()};
+  u.update(this->inner_);
+  return std::unique_ptr<OpaqueCpcSketch>(new OpaqueCpcSketch{u.get_result()});
+}
+
 std::unique_ptr<std::vector<uint8_t>> OpaqueCpcSketch::serialize() const {
+#ifdef DSRS_BYTE_BUFFERS
+  return std::unique_ptr<std::vector<uint8_t>>(new std::vector<uint8_t>(this->inner_.serialize()));
+#else
   // TODO: could use a custom streambuf to avoid the
   // stream -> vec copy https://stackoverflow.com/a/13059195/1779853
   std::stringstream s{};
@@ -47,24 +114,46 @@ std::unique_ptr<std::vector<uint8_t>> OpaqueCpcSketch::serialize() const {
   s.read(reinterpret_cast<char*>(v.data()), std::streamsize(v.size()));
 
   return std::unique_ptr<std::vector<uint8_t>>(new std::vector<uint8_t>(std::move(v)));
+#endif
 }
 
 std::unique_ptr<OpaqueCpcSketch> new_opaque_cpc_sketch() {
   return std::unique_ptr<OpaqueCpcSketch>(new OpaqueCpcSketch{});
 }
 
+double cpc_icon_estimate(uint8_t lg_k, uint32_t num_coupons) {
+  return datasketches::compute_icon_estimate(lg_k, num_coupons);
+}
+
+#ifdef DSRS_BYTE_BUFFERS
 std::unique_ptr<OpaqueCpcSketch> deserialize_opaque_cpc_sketch(rust::Slice<const uint8_t> buf) {
+  auto cpc = datasketches::cpc_sketch::deserialize(buf.data(), buf.size());
+  return std::unique_ptr<OpaqueCpcSketch>(new OpaqueCpcSketch{std::move(cpc)});
+}
+#else
+static void slice_to_stream(rust::Slice<const uint8_t> buf, std::stringstream& s) {
   // TODO: could use a custom streambuf to avoid the slice -> stream copy
-  std::stringstream s{};
   s.write(const_cast<char*>(reinterpret_cast<const char*>(buf.data())), std::streamsize(buf.size()));
   s.seekg(0, std::ios::beg);
+}
+
+std::unique_ptr<OpaqueCpcSketch> deserialize_opaque_cpc_sketch(rust::Slice<const uint8_t> buf) {
+  std::stringstream s{};
+  slice_to_stream(buf, s);
   return std::unique_ptr<OpaqueCpcSketch>(new OpaqueCpcSketch{s});
 }
+#endif
 
 OpaqueCpcUnion::OpaqueCpcUnion():
   inner_{} {
 }
 
+double OpaqueCpcUnion::estimate() const {
+  // the union's result is always marked as merged, so it has no HIP estimate
+  // and its ICON estimate only depends on lg_k and the coupon count
+  return datasketches::compute_icon_estimate(this->inner_.get_lg_k(), this->inner_.get_num_coupons());
+}
+
 std::unique_ptr<OpaqueCpcSketch> OpaqueCpcUnion::sketch() const {
   return std::unique_ptr<OpaqueCpcSketch>(new OpaqueCpcSketch{this->inner_.get_result()});
 }
@@ -73,6 +162,20 @@ void OpaqueCpcUnion::merge(std::unique_ptr<OpaqueCpcSketch> to_add) {
   this->inner_.update(std::move(to_add->inner_));
 }
 
+void OpaqueCpcUnion::merge_ref(const OpaqueCpcSketch& to_add) {
+  this->inner_.update(to_add.inner_);
+}
+
+void OpaqueCpcUnion::merge_serialized(rust::Slice<const uint8_t> buf) {
+#ifdef DSRS_BYTE_BUFFERS
+  this->inner_.update(datasketches::cpc_sketch::deserialize(buf.data(), buf.size()));
+#else
+  std::stringstream s{};
+  slice_to_stream(buf, s);
+  this->inner_.update(datasketches::cpc_sketch::deserialize(s));
+#endif
+}
+
 
 std::unique_ptr<OpaqueCpcUnion> new_opaque_cpc_union() {
   return std::unique_ptr<OpaqueCpcUnion>(new OpaqueCpcUnion{});
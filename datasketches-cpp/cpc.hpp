@@ -1,7 +1,7 @@
 #pragma once
 
 #include <cstdint>
-#include <iostream>
+#include <iosfwd>
 #include <vector>
 #include <memory>
 
@@ -12,13 +12,27 @@
 class OpaqueCpcSketch {
 public:
   double estimate() const;
+  uint8_t get_lg_k() const;
+  bool is_empty() const;
+  double get_lower_bound(uint8_t kappa) const;
+  double get_upper_bound(uint8_t kappa) const;
+  size_t get_allocated_bytes() const;
+  uint8_t get_flavor() const;
   void update(rust::Slice<const uint8_t> buf);
   void update_u64(uint64_t value);
+  void update_u64_slice(rust::Slice<const uint64_t> values);
+  void update_batch(rust::Slice<const uint8_t> buf, rust::Slice<const size_t> ends);
+  void update_prehashed(uint64_t hash);
+  void reset();
+  std::unique_ptr<OpaqueCpcSketch> clone() const;
+  std::unique_ptr<OpaqueCpcSketch> canonical() const;
   std::unique_ptr<std::vector<uint8_t>> serialize() const;
 private:
   OpaqueCpcSketch();
   OpaqueCpcSketch(datasketches::cpc_sketch&& cpc);
+#ifndef DSRS_BYTE_BUFFERS
   OpaqueCpcSketch(std::istream& is);
+#endif
   friend std::unique_ptr<OpaqueCpcSketch> new_opaque_cpc_sketch();
   friend std::unique_ptr<OpaqueCpcSketch> deserialize_opaque_cpc_sketch(rust::Slice<const uint8_t> buf);
   friend class OpaqueCpcUnion;
@@ -27,11 +41,15 @@ private:
 
 std::unique_ptr<OpaqueCpcSketch> new_opaque_cpc_sketch();
 std::unique_ptr<OpaqueCpcSketch> deserialize_opaque_cpc_sketch(rust::Slice<const uint8_t> buf);
+double cpc_icon_estimate(uint8_t lg_k, uint32_t num_coupons);
 
 class OpaqueCpcUnion {
 public:
+  double estimate() const;
   std::unique_ptr<OpaqueCpcSketch> sketch() const;
   void merge(std::unique_ptr<OpaqueCpcSketch> to_add);
+  void merge_ref(const OpaqueCpcSketch& to_add);
+  void merge_serialized(rust::Slice<const uint8_t> buf);
 private:
   OpaqueCpcUnion();
   datasketches::cpc_union inner_;
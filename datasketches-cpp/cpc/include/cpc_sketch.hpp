@@ -82,6 +82,17 @@ public:
    */
   bool is_empty() const;
 
+  /**
+   * @return number of bytes currently allocated on the heap by this sketch
+   */
+  size_t get_allocated_bytes() const;
+
+  /**
+   * @return the sketch's flavor, which reflects how many coupons it has collected
+   * relative to k, as the value of the private flavor enum
+   */
+  uint8_t get_flavor() const;
+
   /**
    * @return estimate of the distinct count of the input stream
    */
@@ -194,6 +205,14 @@ public:
    */
   void update(const void* value, size_t size);
 
+  /**
+   * Update this sketch with a value which has already been hashed to 64 bits,
+   * bypassing the internal hash function. The same hash is used both for the
+   * row and the column of the update.
+   * @param hash the hash of the value to update the sketch with
+   */
+  void update_prehashed(uint64_t hash);
+
   /**
    * Returns a human-readable summary of this sketch
    */
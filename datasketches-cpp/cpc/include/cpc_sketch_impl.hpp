@@ -68,6 +68,16 @@ uint8_t cpc_sketch_alloc<A>::get_lg_k() const {
   return lg_k;
 }
 
+template<typename A>
+size_t cpc_sketch_alloc<A>::get_allocated_bytes() const {
+  return surprising_value_table.get_allocated_bytes() + sliding_window.capacity();
+}
+
+template<typename A>
+uint8_t cpc_sketch_alloc<A>::get_flavor() const {
+  return determine_flavor();
+}
+
 template<typename A>
 bool cpc_sketch_alloc<A>::is_empty() const {
   return num_coupons == 0;
@@ -194,6 +204,11 @@ void cpc_sketch_alloc<A>::update(const void* value, size_t size) {
   row_col_update(row_col_from_two_hashes(hashes.h1, hashes.h2, lg_k));
 }
 
+template<typename A>
+void cpc_sketch_alloc<A>::update_prehashed(uint64_t hash) {
+  row_col_update(row_col_from_two_hashes(hash, hash, lg_k));
+}
+
 template<typename A>
 void cpc_sketch_alloc<A>::row_col_update(uint32_t row_col) {
   const uint8_t col = row_col & 63;
@@ -72,6 +72,17 @@ public:
    */
   cpc_sketch_alloc<A> get_result() const;
 
+  /**
+   * @return base 2 logarithm of the number of bins in the result, which may be smaller
+   * than the union was created with after merging sketches with a smaller lg_k
+   */
+  uint8_t get_lg_k() const;
+
+  /**
+   * @return the number of coupons the result of get_result() would have, without building it
+   */
+  uint32_t get_num_coupons() const;
+
 private:
   typedef typename std::allocator_traits<A>::template rebind_alloc<uint8_t> AllocU8;
   typedef typename std::allocator_traits<A>::template rebind_alloc<uint64_t> AllocU64;
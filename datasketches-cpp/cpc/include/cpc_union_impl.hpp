@@ -177,6 +177,17 @@ cpc_sketch_alloc<A> cpc_union_alloc<A>::get_result() const {
   return get_result_from_bit_matrix();
 }
 
+template<typename A>
+uint8_t cpc_union_alloc<A>::get_lg_k() const {
+  return lg_k;
+}
+
+template<typename A>
+uint32_t cpc_union_alloc<A>::get_num_coupons() const {
+  if (accumulator != nullptr) return accumulator->get_num_coupons();
+  return count_bits_set_in_matrix(bit_matrix.data(), 1 << lg_k);
+}
+
 template<typename A>
 cpc_sketch_alloc<A> cpc_union_alloc<A>::get_result_from_accumulator() const {
   if (lg_k != accumulator->get_lg_k()) throw std::logic_error("lg_k != accumulator->lg_k");
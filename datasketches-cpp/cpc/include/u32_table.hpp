@@ -45,6 +45,7 @@ public:
   inline uint32_t get_num_items() const;
   inline const uint32_t* get_slots() const;
   inline uint8_t get_lg_size() const;
+  inline size_t get_allocated_bytes() const;
   inline void clear();
 
   // returns true iff the item was new and was therefore added to the table
@@ -62,6 +62,11 @@ uint8_t u32_table<A>::get_lg_size() const {
   return lg_size;
 }
 
+template<typename A>
+size_t u32_table<A>::get_allocated_bytes() const {
+  return slots.capacity() * sizeof(uint32_t);
+}
+
 template<typename A>
 void u32_table<A>::clear() {
   std::fill(slots.begin(), slots.end(), UINT32_MAX);
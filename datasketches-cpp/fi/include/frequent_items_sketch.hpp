@@ -273,6 +273,11 @@ public:
 
   void set_weights(W total_weight, W offset) { this->total_weight = total_weight; this->offset = offset; }
   W get_offset() const { return this->offset; }
+  template<typename F>
+  void relink(size_t hashset_addr, F remap) { map.relink(hashset_addr, remap); }
+  uint8_t get_lg_cur_map_size() const { return map.get_lg_cur_size(); }
+  template<typename F>
+  void for_each_in_slot_order(F f) const { map.for_each_in_slot_order(f); }
 
 private:
   static const uint8_t SERIAL_VERSION = 1;
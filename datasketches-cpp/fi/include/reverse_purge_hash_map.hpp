@@ -56,6 +56,18 @@ public:
   uint32_t get_num_active() const;
   const A& get_allocator() const;
 
+  // Replaces each key with remap(key) in place and sends removals to hashset_addr,
+  // e.g., after copying a map whose keys are owned elsewhere. remap must preserve
+  // each key's hash, since keys stay in their slots.
+  template<typename F>
+  void relink(size_t hashset_addr, F remap);
+
+  // Calls f(key, value) for each active slot, starting after an empty one, so that
+  // inserting the keys in this order into an empty map of the same size puts each
+  // key back in its slot.
+  template<typename F>
+  void for_each_in_slot_order(F f) const;
+
   class iterator;
   iterator begin() const;
   iterator end() const;
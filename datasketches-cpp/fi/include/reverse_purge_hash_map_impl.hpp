@@ -75,8 +75,8 @@ states_(nullptr)
       if (other.states_[i] > 0) {
         new (&keys_[i]) K(other.keys_[i]);
         values_[i] = other.values_[i];
+        if (--num == 0) break;
       }
-      if (--num == 0) break;
     }
   }
   std::copy(other.states_, other.states_ + size, states_);
@@ -190,6 +190,29 @@ uint32_t reverse_purge_hash_map<K, V, H, E, A>::get_num_active() const {
   return num_active_;
 }
 
+template<typename K, typename V, typename H, typename E, typename A>
+template<typename F>
+void reverse_purge_hash_map<K, V, H, E, A>::relink(size_t hashset_addr, F remap) {
+  hashset_addr_ = hashset_addr;
+  const uint32_t size = 1 << lg_cur_size_;
+  for (uint32_t i = 0; i < size; i++) {
+    if (is_active(i)) keys_[i] = remap(keys_[i]);
+  }
+}
+
+template<typename K, typename V, typename H, typename E, typename A>
+template<typename F>
+void reverse_purge_hash_map<K, V, H, E, A>::for_each_in_slot_order(F f) const {
+  // the load factor keeps a slot empty, and no key's probe sequence crosses it
+  const uint32_t mask = (1 << lg_cur_size_) - 1;
+  uint32_t empty = 0;
+  while (is_active(empty)) empty++;
+  for (uint32_t i = 1; i <= mask; i++) {
+    const uint32_t index = (empty + i) & mask;
+    if (is_active(index)) f(keys_[index], values_[index]);
+  }
+}
+
 template<typename K, typename V, typename H, typename E, typename A>
 const A& reverse_purge_hash_map<K, V, H, E, A>::get_allocator() const {
   return allocator_;
@@ -1,5 +1,6 @@
+#include <algorithm>
 #include <cstdint>
-#include <iostream>
+#include <stdexcept>
 #include <vector>
 #include <memory>
 
@@ -7,8 +8,19 @@
 
 #include "dsrs/src/bridge.rs.h"
 #include "fi/include/frequent_items_sketch.hpp"
+#include "MurmurHash3.h"
 #include "hh.hpp"
 
+std::size_t InternedKeyHash::operator()(std::size_t addr) const {
+  return interned_key_hash(addr);
+}
+
+uint64_t hh_key_hash(rust::Slice<const uint8_t> value, uint64_t seed) {
+  HashState hashes;
+  MurmurHash3_x64_128(value.data(), value.size(), seed, hashes);
+  return hashes.h1;
+}
+
 std::unique_ptr<std::vector<ThinHeavyHitterRow>> convert_to_thin(OpaqueHhSketch::hhsketch::vector_row v) {
   std::vector<ThinHeavyHitterRow> result(v.size());
   for (std::size_t i = 0; i < v.size(); ++i) {
@@ -50,12 +62,42 @@ uint64_t OpaqueHhSketch::get_total_weight() const {
   return this->inner_.get_total_weight();
 }
 
+uint32_t OpaqueHhSketch::get_num_active_items() const {
+  return this->inner_.get_num_active_items();
+}
+
 uint64_t OpaqueHhSketch::get_offset() const {
   return this->inner_.get_offset();
 }
 
-std::unique_ptr<OpaqueHhSketch> new_opaque_hh_sketch(uint8_t lg2_k, size_t hashset_addr) {
-  OpaqueHhSketch::hhsketch sketch(lg2_k, hashset_addr);
+uint8_t OpaqueHhSketch::get_lg_cur_size() const {
+  return this->inner_.get_lg_cur_map_size();
+}
+
+std::unique_ptr<std::vector<ThinHeavyHitterRow>> OpaqueHhSketch::slot_order_state() const {
+  std::vector<ThinHeavyHitterRow> result;
+  result.reserve(this->inner_.get_num_active_items());
+  const uint64_t offset = this->inner_.get_offset();
+  this->inner_.for_each_in_slot_order([&](size_t addr, uint64_t weight) {
+    result.push_back(ThinHeavyHitterRow{addr, weight, weight + offset});
+  });
+  auto ptr = new std::vector<ThinHeavyHitterRow>(std::move(result));
+  return std::unique_ptr<std::vector<ThinHeavyHitterRow>>(ptr);
+}
+
+std::unique_ptr<OpaqueHhSketch> OpaqueHhSketch::clone_relinked(size_t hashset_addr,
+    rust::Slice<const size_t> old_addrs, rust::Slice<const size_t> new_addrs) const {
+  hhsketch sketch(this->inner_);
+  sketch.relink(hashset_addr, [&](size_t addr) {
+    auto it = std::lower_bound(old_addrs.begin(), old_addrs.end(), addr);
+    if (it == old_addrs.end() || *it != addr) throw std::logic_error("key missing from intern");
+    return new_addrs[it - old_addrs.begin()];
+  });
+  return std::unique_ptr<OpaqueHhSketch>(new OpaqueHhSketch(std::move(sketch)));
+}
+
+std::unique_ptr<OpaqueHhSketch> new_opaque_hh_sketch(uint8_t lg2_k, uint8_t lg_start_size, size_t hashset_addr) {
+  OpaqueHhSketch::hhsketch sketch(lg2_k, hashset_addr, lg_start_size);
   auto ptr = new OpaqueHhSketch(std::move(sketch));
   return std::unique_ptr<OpaqueHhSketch>(ptr);
 }
@@ -10,20 +10,42 @@
 
 struct ThinHeavyHitterRow;
 
+// Keys are addresses of byte strings interned on the Rust side. Hashing
+// the addresses themselves would make the sketch's table layout, and with
+// it the result of purges, depend on allocator behavior, so instead the
+// (seeded) hash of the contents, which Rust stores with each key, is used.
+struct InternedKeyHash {
+  std::size_t operator()(std::size_t addr) const;
+};
+
 class OpaqueHhSketch {
 public:
-  typedef datasketches::frequent_items_sketch<size_t> hhsketch;
+  typedef datasketches::frequent_items_sketch<size_t, uint64_t, InternedKeyHash> hhsketch;
   std::unique_ptr<std::vector<ThinHeavyHitterRow>> estimate_no_fp() const;
   std::unique_ptr<std::vector<ThinHeavyHitterRow>> estimate_no_fn() const;
   void update(size_t value, uint64_t weight);
   std::unique_ptr<std::vector<ThinHeavyHitterRow>> state() const;
   void set_weights(uint64_t total_weight, uint64_t offset);
   uint64_t get_total_weight() const;
+  uint32_t get_num_active_items() const;
   uint64_t get_offset() const;
+  uint8_t get_lg_cur_size() const;
+  // Like state(), but in the order which, replayed into a sketch created with the
+  // same current size, restores each item to its slot in the table.
+  std::unique_ptr<std::vector<ThinHeavyHitterRow>> slot_order_state() const;
+  // Copies the sketch, replacing each key old_addrs[i] with new_addrs[i], where
+  // old_addrs is sorted, and sending removals to the intern at hashset_addr.
+  std::unique_ptr<OpaqueHhSketch> clone_relinked(size_t hashset_addr,
+      rust::Slice<const size_t> old_addrs, rust::Slice<const size_t> new_addrs) const;
 private:
   OpaqueHhSketch(hhsketch&& theta);
-  friend std::unique_ptr<OpaqueHhSketch> new_opaque_hh_sketch(uint8_t lg2_k, size_t hashset_addr);
+  friend std::unique_ptr<OpaqueHhSketch> new_opaque_hh_sketch(uint8_t lg2_k, uint8_t lg_start_size, size_t hashset_addr);
   hhsketch inner_;
 };
 
-std::unique_ptr<OpaqueHhSketch> new_opaque_hh_sketch(uint8_t lg2_k, size_t hashset_addr);
+std::unique_ptr<OpaqueHhSketch> new_opaque_hh_sketch(uint8_t lg2_k, uint8_t lg_start_size, size_t hashset_addr);
+
+// The seeded hash of a key's contents, which InternedKeyHash returns for its
+// interned copy. Unlike std::hash, MurmurHash3 is fully specified, so the hash,
+// and with it the sketch's layout, doesn't change between builds.
+uint64_t hh_key_hash(rust::Slice<const uint8_t> value, uint64_t seed);
@@ -1,5 +1,7 @@
 #include <cstdint>
-#include <iostream>
+#ifndef DSRS_BYTE_BUFFERS
+#include <sstream>
+#endif
 #include <vector>
 #include <memory>
 
@@ -15,32 +17,68 @@ double OpaqueThetaSketch::estimate() const {
   return this->inner_.get_estimate();
 }
 
+bool OpaqueThetaSketch::is_empty() const {
+  return this->inner_.is_empty();
+}
+
+size_t OpaqueThetaSketch::get_allocated_bytes() const {
+  return this->inner_.get_allocated_bytes();
+}
+
 void OpaqueThetaSketch::update(rust::Slice<const uint8_t> buf) {
   this->inner_.update(buf.data(), buf.size());
 }
 
+void OpaqueThetaSketch::update_batch(rust::Slice<const uint8_t> buf, rust::Slice<const size_t> ends) {
+  size_t start = 0;
+  for (auto end : ends) {
+    this->inner_.update(buf.data() + start, end - start);
+    start = end;
+  }
+}
+
 void OpaqueThetaSketch::update_u64(uint64_t value) {
   this->inner_.update(value);
 }
 
+void OpaqueThetaSketch::update_u64_slice(rust::Slice<const uint64_t> values) {
+  for (auto value : values) {
+    this->inner_.update(value);
+  }
+}
+
 std::unique_ptr<OpaqueStaticThetaSketch> OpaqueThetaSketch::as_static() const{
   auto compact = this->inner_.compact();
   auto ptr = new OpaqueStaticThetaSketch{std::move(compact)};
   return std::unique_ptr<OpaqueStaticThetaSketch>(ptr);
 }
 
+void OpaqueThetaSketch::reset() {
+  // there's no in-place reset in datasketches-cpp, so rebuild in place
+  this->inner_ = datasketches::update_theta_sketch::builder{}.set_lg_k(this->inner_.get_lg_k()).set_p(this->p_).build();
+}
+
+void OpaqueThetaSketch::absorb(const OpaqueStaticThetaSketch& other) {
+  this->inner_.absorb(other.inner_);
+}
+
 OpaqueThetaSketch::OpaqueThetaSketch():
-  inner_{datasketches::update_theta_sketch::builder{}.build()} {
+  OpaqueThetaSketch{datasketches::update_theta_sketch::builder::DEFAULT_LG_K, 1.0} {
 }
 
-OpaqueThetaSketch::OpaqueThetaSketch(datasketches::update_theta_sketch&& theta):
-  inner_{std::move(theta)} {
+OpaqueThetaSketch::OpaqueThetaSketch(uint8_t lg_k, float p):
+  inner_{datasketches::update_theta_sketch::builder{}.set_lg_k(lg_k).set_p(p).build()},
+  p_{p} {
 }
 
 std::unique_ptr<OpaqueThetaSketch> new_opaque_theta_sketch() {
   return std::unique_ptr<OpaqueThetaSketch>(new OpaqueThetaSketch{});
 }
 
+std::unique_ptr<OpaqueThetaSketch> new_opaque_theta_sketch_with_p(uint8_t lg_k, float p) {
+  return std::unique_ptr<OpaqueThetaSketch>(new OpaqueThetaSketch{lg_k, p});
+}
+
 OpaqueStaticThetaSketch::OpaqueStaticThetaSketch(const datasketches::compact_theta_sketch& theta):
   inner_{theta} {
 }
@@ -49,14 +87,36 @@ OpaqueStaticThetaSketch::OpaqueStaticThetaSketch(datasketches::compact_theta_ske
   inner_{std::move(theta)} {
 }
 
+#ifndef DSRS_BYTE_BUFFERS
 OpaqueStaticThetaSketch::OpaqueStaticThetaSketch(std::istream& is):
   inner_{datasketches::compact_theta_sketch::deserialize(is)} {
 }
+#endif
 
 double OpaqueStaticThetaSketch::estimate() const {
   return this->inner_.get_estimate();
 }
 
+bool OpaqueStaticThetaSketch::is_empty() const {
+  return this->inner_.is_empty();
+}
+
+bool OpaqueStaticThetaSketch::is_ordered() const {
+  return this->inner_.is_ordered();
+}
+
+double OpaqueStaticThetaSketch::get_theta() const {
+  return this->inner_.get_theta();
+}
+
+uint32_t OpaqueStaticThetaSketch::get_num_retained() const {
+  return this->inner_.get_num_retained();
+}
+
+size_t OpaqueStaticThetaSketch::get_allocated_bytes() const {
+  return this->inner_.get_allocated_bytes();
+}
+
 std::unique_ptr<OpaqueStaticThetaSketch> OpaqueStaticThetaSketch::clone() const {
   return std::unique_ptr<OpaqueStaticThetaSketch>(new OpaqueStaticThetaSketch{this->inner_});
 }
@@ -88,11 +148,16 @@ std::unique_ptr<std::vector<uint8_t>> OpaqueStaticThetaSketch::serialize() const
 }
 
 std::unique_ptr<OpaqueStaticThetaSketch> deserialize_opaque_static_theta_sketch(rust::Slice<const uint8_t> buf) {
+#ifdef DSRS_BYTE_BUFFERS
+  auto theta = datasketches::compact_theta_sketch::deserialize(buf.data(), buf.size());
+  return std::unique_ptr<OpaqueStaticThetaSketch>(new OpaqueStaticThetaSketch{std::move(theta)});
+#else
   // TODO: could use a custom streambuf to avoid the slice -> stream copy
   std::stringstream s{};
   s.write(const_cast<char*>(reinterpret_cast<const char*>(buf.data())), std::streamsize(buf.size()));
   s.seekg(0, std::ios::beg);
   return std::unique_ptr<OpaqueStaticThetaSketch>(new OpaqueStaticThetaSketch{s});
+#endif
 }
 
 OpaqueThetaUnion::OpaqueThetaUnion():
@@ -126,6 +191,10 @@ std::unique_ptr<OpaqueStaticThetaSketch> OpaqueThetaIntersection::sketch() const
   return std::unique_ptr<OpaqueStaticThetaSketch>(ptr);
 }
 
+bool OpaqueThetaIntersection::has_result() const {
+  return this->inner_.has_result();
+}
+
 void OpaqueThetaIntersection::intersect_with(std::unique_ptr<OpaqueStaticThetaSketch> to_intersect) {
   this->inner_.update(std::move(to_intersect->inner_));
 }
@@ -1,7 +1,7 @@
 #pragma once
 
 #include <cstdint>
-#include <iostream>
+#include <iosfwd>
 #include <vector>
 #include <memory>
 
@@ -16,28 +16,45 @@ class OpaqueStaticThetaSketch;
 class OpaqueThetaSketch {
 public:
   double estimate() const;
+  bool is_empty() const;
+  size_t get_allocated_bytes() const;
   void update(rust::Slice<const uint8_t> buf);
   void update_u64(uint64_t value);
+  void update_u64_slice(rust::Slice<const uint64_t> values);
+  void update_batch(rust::Slice<const uint8_t> buf, rust::Slice<const size_t> ends);
+  void reset();
+  void absorb(const OpaqueStaticThetaSketch& other);
   std::unique_ptr<OpaqueStaticThetaSketch> as_static() const;
 private:
   OpaqueThetaSketch();
-  OpaqueThetaSketch(datasketches::update_theta_sketch&& theta);
+  OpaqueThetaSketch(uint8_t lg_k, float p);
   friend std::unique_ptr<OpaqueThetaSketch> new_opaque_theta_sketch();
+  friend std::unique_ptr<OpaqueThetaSketch> new_opaque_theta_sketch_with_p(uint8_t lg_k, float p);
   datasketches::update_theta_sketch inner_;
+  // not retrievable from the sketch, but needed to reset it
+  float p_;
 };
 
 std::unique_ptr<OpaqueThetaSketch> new_opaque_theta_sketch();
+std::unique_ptr<OpaqueThetaSketch> new_opaque_theta_sketch_with_p(uint8_t lg_k, float p);
 
 class OpaqueStaticThetaSketch {
 public:
   double estimate() const;
+  bool is_empty() const;
+  bool is_ordered() const;
+  double get_theta() const;
+  uint32_t get_num_retained() const;
+  size_t get_allocated_bytes() const;
   std::unique_ptr<OpaqueStaticThetaSketch> clone() const;
   void set_difference(const OpaqueStaticThetaSketch& other);
   std::unique_ptr<std::vector<uint8_t>> serialize() const;
 private:
   OpaqueStaticThetaSketch(const datasketches::compact_theta_sketch& theta);
   OpaqueStaticThetaSketch(datasketches::compact_theta_sketch&& theta);
+#ifndef DSRS_BYTE_BUFFERS
   OpaqueStaticThetaSketch(std::istream& is);
+#endif
   friend std::unique_ptr<OpaqueStaticThetaSketch> deserialize_opaque_static_theta_sketch(rust::Slice<const uint8_t> buf);
   friend class OpaqueThetaSketch;
   friend class OpaqueThetaUnion;
@@ -64,6 +81,7 @@ public:
   // Null if the intersection is over an empty collection, i.e., the sketch
   // implicitly represents the full universe of items.
   std::unique_ptr<OpaqueStaticThetaSketch> sketch() const;
+  bool has_result() const;
   void intersect_with(std::unique_ptr<OpaqueStaticThetaSketch> to_intersect);
 private:
   OpaqueThetaIntersection();
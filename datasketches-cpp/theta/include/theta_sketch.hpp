@@ -170,6 +170,11 @@ public:
    */
   uint8_t get_lg_k() const;
 
+  /**
+   * @return number of bytes currently allocated on the heap by this sketch
+   */
+  size_t get_allocated_bytes() const;
+
   /**
    * @return configured resize factor of the sketch
    */
@@ -269,6 +274,14 @@ public:
    */
   void trim();
 
+  /**
+   * Update this sketch with the retained hashes of another sketch with the same seed.
+   * Theta becomes the minimum of the two, so retained hashes at or above the other
+   * sketch's theta are dropped.
+   * @param other sketch to absorb
+   */
+  void absorb(const theta_sketch_alloc<Allocator>& other);
+
   /**
    * Converts this sketch to a compact sketch (ordered or unordered).
    * @param ordered optional flag to specify if ordered sketch should be produced
@@ -325,6 +338,11 @@ public:
   virtual uint32_t get_num_retained() const;
   virtual uint16_t get_seed_hash() const;
 
+  /**
+   * @return number of bytes currently allocated on the heap by this sketch
+   */
+  size_t get_allocated_bytes() const;
+
   /**
    * This method serializes the sketch into a given stream in a binary form
    * @param os output stream
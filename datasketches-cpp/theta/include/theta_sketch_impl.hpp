@@ -125,6 +125,11 @@ uint8_t update_theta_sketch_alloc<A>::get_lg_k() const {
   return table_.lg_nom_size_;
 }
 
+template<typename A>
+size_t update_theta_sketch_alloc<A>::get_allocated_bytes() const {
+  return table_.entries_ == nullptr ? 0 : sizeof(uint64_t) << table_.lg_cur_size_;
+}
+
 template<typename A>
 auto update_theta_sketch_alloc<A>::get_rf() const -> resize_factor {
   return table_.rf_;
@@ -201,6 +206,33 @@ void update_theta_sketch_alloc<A>::trim() {
   table_.trim();
 }
 
+template<typename A>
+void update_theta_sketch_alloc<A>::absorb(const theta_sketch_alloc<A>& other) {
+  if (other.is_empty()) return;
+  if (other.get_seed_hash() != get_seed_hash()) throw std::invalid_argument("seed hash mismatch");
+  table_.is_empty_ = false;
+  const uint64_t other_theta = other.get_theta64();
+  if (other_theta < table_.theta_) {
+    // entries can't be removed in place without breaking probe sequences, so reinsert survivors
+    std::vector<uint64_t, A> kept(table_.allocator_);
+    for (uint64_t hash: table_) {
+      if (hash != 0 && hash < other_theta) kept.push_back(hash);
+    }
+    for (auto& entry: table_) entry = 0;
+    table_.num_entries_ = 0;
+    table_.theta_ = other_theta;
+    for (uint64_t hash: kept) table_.insert(table_.find(hash).first, hash);
+  }
+  for (uint64_t hash: other) {
+    if (hash < table_.theta_) {
+      auto result = table_.find(hash);
+      if (!result.second) table_.insert(result.first, hash);
+    } else if (other.is_ordered()) {
+      break;
+    }
+  }
+}
+
 template<typename A>
 auto update_theta_sketch_alloc<A>::begin() -> iterator {
   return iterator(table_.entries_, 1 << table_.lg_cur_size_, 0);
@@ -298,6 +330,11 @@ uint16_t compact_theta_sketch_alloc<A>::get_seed_hash() const {
   return seed_hash_;
 }
 
+template<typename A>
+size_t compact_theta_sketch_alloc<A>::get_allocated_bytes() const {
+  return entries_.capacity() * sizeof(uint64_t);
+}
+
 template<typename A>
 auto compact_theta_sketch_alloc<A>::begin() -> iterator {
   return iterator(entries_.data(), static_cast<uint32_t>(entries_.size()), 0);
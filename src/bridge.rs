@@ -2,7 +2,7 @@
 //!
 //! See [`crate::wrapper`] for external Rust-friendly types.
 
-use crate::wrapper::hh::remove_from_hashset;
+use crate::wrapper::hh::{interned_key_hash, remove_from_hashset};
 
 #[cxx::bridge]
 pub(crate) mod ffi {
@@ -16,6 +16,7 @@ pub(crate) mod ffi {
 
     extern "Rust" {
         unsafe fn remove_from_hashset(hashset_addr: usize, addr: usize);
+        unsafe fn interned_key_hash(addr: usize) -> u64;
     }
 
     unsafe extern "C++" {
@@ -24,31 +25,71 @@ pub(crate) mod ffi {
         pub(crate) type OpaqueCpcSketch;
 
         pub(crate) fn new_opaque_cpc_sketch() -> UniquePtr<OpaqueCpcSketch>;
-        pub(crate) fn deserialize_opaque_cpc_sketch(buf: &[u8]) -> UniquePtr<OpaqueCpcSketch>;
+        pub(crate) fn deserialize_opaque_cpc_sketch(
+            buf: &[u8],
+        ) -> Result<UniquePtr<OpaqueCpcSketch>>;
+        pub(crate) fn cpc_icon_estimate(lg_k: u8, num_coupons: u32) -> f64;
         pub(crate) fn estimate(self: &OpaqueCpcSketch) -> f64;
+        pub(crate) fn get_lg_k(self: &OpaqueCpcSketch) -> u8;
+        pub(crate) fn is_empty(self: &OpaqueCpcSketch) -> bool;
+        pub(crate) fn get_lower_bound(self: &OpaqueCpcSketch, kappa: u8) -> f64;
+        pub(crate) fn get_upper_bound(self: &OpaqueCpcSketch, kappa: u8) -> f64;
+        pub(crate) fn get_allocated_bytes(self: &OpaqueCpcSketch) -> usize;
+        pub(crate) fn get_flavor(self: &OpaqueCpcSketch) -> u8;
         pub(crate) fn update(self: Pin<&mut OpaqueCpcSketch>, buf: &[u8]);
         pub(crate) fn update_u64(self: Pin<&mut OpaqueCpcSketch>, value: u64);
+        pub(crate) fn update_u64_slice(self: Pin<&mut OpaqueCpcSketch>, values: &[u64]);
+        pub(crate) fn update_batch(self: Pin<&mut OpaqueCpcSketch>, buf: &[u8], ends: &[usize]);
+        pub(crate) fn update_prehashed(self: Pin<&mut OpaqueCpcSketch>, hash: u64);
+        pub(crate) fn reset(self: Pin<&mut OpaqueCpcSketch>);
+        pub(crate) fn clone(self: &OpaqueCpcSketch) -> UniquePtr<OpaqueCpcSketch>;
+        pub(crate) fn canonical(self: &OpaqueCpcSketch) -> UniquePtr<OpaqueCpcSketch>;
         pub(crate) fn serialize(self: &OpaqueCpcSketch) -> UniquePtr<CxxVector<u8>>;
 
         pub(crate) type OpaqueCpcUnion;
 
         pub(crate) fn new_opaque_cpc_union() -> UniquePtr<OpaqueCpcUnion>;
+        pub(crate) fn estimate(self: &OpaqueCpcUnion) -> f64;
         pub(crate) fn sketch(self: &OpaqueCpcUnion) -> UniquePtr<OpaqueCpcSketch>;
-        pub(crate) fn merge(self: Pin<&mut OpaqueCpcUnion>, to_add: UniquePtr<OpaqueCpcSketch>);
+        pub(crate) fn merge(
+            self: Pin<&mut OpaqueCpcUnion>,
+            to_add: UniquePtr<OpaqueCpcSketch>,
+        ) -> Result<()>;
+        pub(crate) fn merge_ref(self: Pin<&mut OpaqueCpcUnion>, to_add: &OpaqueCpcSketch)
+            -> Result<()>;
+        pub(crate) fn merge_serialized(self: Pin<&mut OpaqueCpcUnion>, buf: &[u8]) -> Result<()>;
 
         include!("dsrs/datasketches-cpp/theta.hpp");
 
         pub(crate) type OpaqueThetaSketch;
 
         pub(crate) fn new_opaque_theta_sketch() -> UniquePtr<OpaqueThetaSketch>;
+        pub(crate) fn new_opaque_theta_sketch_with_p(
+            lg_k: u8,
+            p: f32,
+        ) -> Result<UniquePtr<OpaqueThetaSketch>>;
         pub(crate) fn estimate(self: &OpaqueThetaSketch) -> f64;
+        pub(crate) fn is_empty(self: &OpaqueThetaSketch) -> bool;
+        pub(crate) fn get_allocated_bytes(self: &OpaqueThetaSketch) -> usize;
         pub(crate) fn update(self: Pin<&mut OpaqueThetaSketch>, buf: &[u8]);
         pub(crate) fn update_u64(self: Pin<&mut OpaqueThetaSketch>, value: u64);
+        pub(crate) fn update_u64_slice(self: Pin<&mut OpaqueThetaSketch>, values: &[u64]);
+        pub(crate) fn update_batch(self: Pin<&mut OpaqueThetaSketch>, buf: &[u8], ends: &[usize]);
+        pub(crate) fn reset(self: Pin<&mut OpaqueThetaSketch>);
+        pub(crate) fn absorb(
+            self: Pin<&mut OpaqueThetaSketch>,
+            other: &OpaqueStaticThetaSketch,
+        ) -> Result<()>;
         pub(crate) fn as_static(self: &OpaqueThetaSketch) -> UniquePtr<OpaqueStaticThetaSketch>;
 
         pub(crate) type OpaqueStaticThetaSketch;
 
         pub(crate) fn estimate(self: &OpaqueStaticThetaSketch) -> f64;
+        pub(crate) fn is_empty(self: &OpaqueStaticThetaSketch) -> bool;
+        pub(crate) fn is_ordered(self: &OpaqueStaticThetaSketch) -> bool;
+        pub(crate) fn get_theta(self: &OpaqueStaticThetaSketch) -> f64;
+        pub(crate) fn get_num_retained(self: &OpaqueStaticThetaSketch) -> u32;
+        pub(crate) fn get_allocated_bytes(self: &OpaqueStaticThetaSketch) -> usize;
         pub(crate) fn clone(self: &OpaqueStaticThetaSketch) -> UniquePtr<OpaqueStaticThetaSketch>;
         pub(crate) fn set_difference(
             self: Pin<&mut OpaqueStaticThetaSketch>,
@@ -57,7 +98,7 @@ pub(crate) mod ffi {
         pub(crate) fn serialize(self: &OpaqueStaticThetaSketch) -> UniquePtr<CxxVector<u8>>;
         pub(crate) fn deserialize_opaque_static_theta_sketch(
             buf: &[u8],
-        ) -> UniquePtr<OpaqueStaticThetaSketch>;
+        ) -> Result<UniquePtr<OpaqueStaticThetaSketch>>;
 
         pub(crate) type OpaqueThetaUnion;
 
@@ -66,22 +107,28 @@ pub(crate) mod ffi {
         pub(crate) fn union_with(
             self: Pin<&mut OpaqueThetaUnion>,
             to_union: UniquePtr<OpaqueStaticThetaSketch>,
-        );
+        ) -> Result<()>;
 
         pub(crate) type OpaqueThetaIntersection;
 
         pub(crate) fn new_opaque_theta_intersection() -> UniquePtr<OpaqueThetaIntersection>;
         pub(crate) fn sketch(self: &OpaqueThetaIntersection) -> UniquePtr<OpaqueStaticThetaSketch>;
+        pub(crate) fn has_result(self: &OpaqueThetaIntersection) -> bool;
         pub(crate) fn intersect_with(
             self: Pin<&mut OpaqueThetaIntersection>,
             to_intersect: UniquePtr<OpaqueStaticThetaSketch>,
-        );
+        ) -> Result<()>;
 
         include!("dsrs/datasketches-cpp/hh.hpp");
 
         pub(crate) type OpaqueHhSketch;
 
-        pub(crate) fn new_opaque_hh_sketch(lg2_k: u8, hashset_addr: usize) -> UniquePtr<OpaqueHhSketch>;
+        pub(crate) fn new_opaque_hh_sketch(
+            lg2_k: u8,
+            lg_start_size: u8,
+            hashset_addr: usize,
+        ) -> Result<UniquePtr<OpaqueHhSketch>>;
+        pub(crate) fn hh_key_hash(value: &[u8], seed: u64) -> u64;
         pub(crate) fn estimate_no_fp(
             self: &OpaqueHhSketch,
         ) -> UniquePtr<CxxVector<ThinHeavyHitterRow>>;
@@ -94,6 +141,17 @@ pub(crate) mod ffi {
         pub(crate) fn update(self: Pin<&mut OpaqueHhSketch>, value: usize, weight: u64);
         pub(crate) fn set_weights(self: Pin<&mut OpaqueHhSketch>, total_weight: u64, weight: u64);
         pub(crate) fn get_total_weight(self: &OpaqueHhSketch) -> u64;
+        pub(crate) fn get_num_active_items(self: &OpaqueHhSketch) -> u32;
         pub(crate) fn get_offset(self: &OpaqueHhSketch) -> u64;
+        pub(crate) fn get_lg_cur_size(self: &OpaqueHhSketch) -> u8;
+        pub(crate) fn slot_order_state(
+            self: &OpaqueHhSketch,
+        ) -> UniquePtr<CxxVector<ThinHeavyHitterRow>>;
+        pub(crate) fn clone_relinked(
+            self: &OpaqueHhSketch,
+            hashset_addr: usize,
+            old_addrs: &[usize],
+            new_addrs: &[usize],
+        ) -> Result<UniquePtr<OpaqueHhSketch>>;
     }
 }
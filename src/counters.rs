@@ -2,28 +2,21 @@
 //! hitters sketches, aimed at servicing the `dsrs` command-line tool
 //! for deduplicating byte lines of input.
 
-use std::collections::HashMap;
+use std::borrow::Borrow;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
 use std::convert::TryInto;
+use std::mem;
 use std::str;
 
-use base64;
-use memchr;
-
-use crate::stream_reducer::LineReducer;
-use crate::{CpcSketch, CpcUnion, HhSketch};
+use crate::stream_reducer::{Checkpointable, LineReducer};
+use crate::{CpcSketch, CpcSummary, CpcUnion, DataSketchesError, HhErrorType, HhSketch};
 
+#[derive(Clone, Default)]
 pub struct Counter {
     sketch: CpcSketch,
 }
 
-impl Default for Counter {
-    fn default() -> Self {
-        Self {
-            sketch: CpcSketch::new(),
-        }
-    }
-}
-
 impl Counter {
     /// Serializes to base64 string with no newlines or `=` padding.
     pub fn serialize(&self) -> String {
@@ -34,7 +27,7 @@ impl Counter {
     /// Deserializes from base64 string with no newlines or `=` padding.
     pub fn deserialize(s: &str) -> Result<Self, base64::DecodeError> {
         let bytes = base64::decode_config(s, base64::STANDARD_NO_PAD)?;
-        let sketch = CpcSketch::deserialize(bytes.as_ref());
+        let sketch = CpcSketch::deserialize(bytes);
         Ok(Self { sketch })
     }
 
@@ -42,6 +35,38 @@ impl Counter {
     pub fn estimate(&self) -> f64 {
         self.sketch.estimate()
     }
+
+    /// Returns the current row estimate, rounded as by
+    /// [`CpcSketch::estimate_rounded`].
+    pub fn estimate_rounded(&self) -> u64 {
+        self.sketch.estimate_rounded()
+    }
+
+    /// Returns a lower bound for the row estimate, two standard deviations
+    /// below it.
+    pub fn lower_bound(&self) -> f64 {
+        self.sketch.get_lower_bound(2)
+    }
+
+    /// Returns an upper bound for the row estimate, two standard deviations
+    /// above it.
+    pub fn upper_bound(&self) -> f64 {
+        self.sketch.get_upper_bound(2)
+    }
+
+    /// Returns the underlying sketch's parameters and state.
+    pub fn summary(&self) -> CpcSummary {
+        self.sketch.summary()
+    }
+
+    /// Merges `other` into this counter in memory, so that this counter
+    /// estimates the distinct rows seen by either.
+    pub fn merge(&mut self, other: &Counter) {
+        let mut union = CpcUnion::new();
+        union.merge(mem::take(&mut self.sketch));
+        union.merge_ref(&other.sketch);
+        self.sketch = union.sketch();
+    }
 }
 
 impl LineReducer for Counter {
@@ -50,9 +75,48 @@ impl LineReducer for Counter {
     }
 }
 
+/// The checkpoint is the serialized CPC sketch.
+impl Checkpointable for Counter {
+    fn checkpoint(&self) -> Vec<u8> {
+        self.sketch.serialize_to_vec()
+    }
+
+    fn restore(bytes: &[u8]) -> Result<Self, DataSketchesError> {
+        let sketch = CpcSketch::try_deserialize(bytes)?;
+        Ok(Self { sketch })
+    }
+}
+
+/// A key and its estimate, ordered by estimate, and for equal estimates,
+/// with smaller keys ordered higher, for selecting the top keys with a heap.
+struct ByEstimate<'a>(f64, &'a Vec<u8>);
+
+impl Ord for ByEstimate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .total_cmp(&other.0)
+            .then_with(|| other.1.cmp(self.1))
+    }
+}
+
+impl PartialOrd for ByEstimate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ByEstimate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ByEstimate<'_> {}
+
 #[derive(Default)]
 pub struct KeyedCounter {
     sketches: HashMap<Vec<u8>, Counter>,
+    skip_empty: bool,
 }
 
 impl LineReducer for KeyedCounter {
@@ -64,6 +128,9 @@ impl LineReducer for KeyedCounter {
             )
         });
         let (key, value) = (&line[0..space_ix], &line[space_ix + 1..]);
+        if self.skip_empty && value.is_empty() {
+            return;
+        }
         if !self.sketches.contains_key(key) {
             self.sketches.insert(key.to_owned(), Counter::default());
         }
@@ -75,29 +142,165 @@ impl LineReducer for KeyedCounter {
 }
 
 impl KeyedCounter {
+    /// Creates a keyed counter which ignores lines with an empty value,
+    /// i.e., which end right after the key's delimiting space. Keys seen
+    /// only on such lines are not reported.
+    pub fn skipping_empty() -> Self {
+        Self {
+            skip_empty: true,
+            ..Self::default()
+        }
+    }
+
+    /// Creates a keyed counter with room for `n` keys before reallocating.
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            sketches: HashMap::with_capacity(n),
+            ..Self::default()
+        }
+    }
+
+    /// Reserves room for at least `additional` more keys, e.g., when the
+    /// number of keys is known ahead of time.
+    pub fn reserve(&mut self, additional: usize) {
+        self.sketches.reserve(additional)
+    }
+
     /// Returns an iterator over all contained keys and their sketches.
     pub fn state(&self) -> impl Iterator<Item = (&[u8], &Counter)> {
         self.sketches.iter().map(|(key, ctr)| (key.as_ref(), ctr))
     }
-}
 
-pub struct Merger {
-    sketch: CpcUnion,
-}
+    /// Returns the counter for `key`, if it was seen.
+    pub fn get(&self, key: &[u8]) -> Option<&Counter> {
+        self.sketches.get(key)
+    }
 
-impl Default for Merger {
-    fn default() -> Self {
-        Self {
-            sketch: CpcUnion::new(),
+    /// Returns the (at most) `n` keys with the largest estimates, along with
+    /// those estimates, in descending order of estimate, with ties broken by
+    /// key bytes in ascending order.
+    ///
+    /// Selection uses a bounded heap, so this takes `O(m log n)` time for `m`
+    /// keys.
+    pub fn top_keys(&self, n: usize) -> Vec<(Vec<u8>, f64)> {
+        if n == 0 {
+            return Vec::new();
+        }
+        let mut heap = BinaryHeap::with_capacity(n.min(self.sketches.len()) + 1);
+        for (key, ctr) in &self.sketches {
+            heap.push(Reverse(ByEstimate(ctr.estimate(), key)));
+            if heap.len() > n {
+                heap.pop();
+            }
         }
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(ByEstimate(estimate, key))| (key.clone(), estimate))
+            .collect()
+    }
+
+    /// Returns the number of distinct keys seen.
+    pub fn len(&self) -> usize {
+        self.sketches.len()
+    }
+
+    /// Returns whether no keys have been seen.
+    pub fn is_empty(&self) -> bool {
+        self.sketches.is_empty()
+    }
+
+    /// Serializes all keys and their sketches into a single buffer. Each
+    /// key is followed by its serialized sketch, both prefixed by their
+    /// length as a little-endian `u64`, so keys may hold arbitrary bytes.
+    /// Keys are written in sorted order, so equal counters serialize equally.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut entries: Vec<_> = self.sketches.iter().collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        let mut buf = Vec::new();
+        for (key, ctr) in entries {
+            for bytes in &[key.as_ref(), ctr.sketch.serialize().as_ref()] {
+                buf.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+                buf.extend_from_slice(bytes);
+            }
+        }
+        buf
+    }
+
+    /// Deserializes a keyed counter written by [`Self::serialize`]. Whether
+    /// the counter skipped empty values isn't recorded, so the result
+    /// counts them.
+    pub fn deserialize(mut buf: &[u8]) -> Result<Self, DataSketchesError> {
+        fn next_frame<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], DataSketchesError> {
+            let truncated = || DataSketchesError::InvalidBuffer("truncated frame".to_owned());
+            if buf.len() < 8 {
+                return Err(truncated());
+            }
+            let (len, rest) = buf.split_at(8);
+            let len = u64::from_le_bytes(len.try_into().expect("8 bytes"));
+            let len: Option<usize> = len.try_into().ok();
+            let len = len.filter(|len| *len <= rest.len());
+            let (frame, rest) = rest.split_at(len.ok_or_else(truncated)?);
+            *buf = rest;
+            Ok(frame)
+        }
+
+        let mut counter = Self::default();
+        while !buf.is_empty() {
+            let key = next_frame(&mut buf)?;
+            let sketch = CpcSketch::try_deserialize(next_frame(&mut buf)?)?;
+            counter.sketches.insert(key.to_owned(), Counter { sketch });
+        }
+        Ok(counter)
+    }
+}
+
+/// Unlike [`KeyedCounter::serialize`], the checkpoint also records whether
+/// the counter skips empty values, in a leading byte.
+impl Checkpointable for KeyedCounter {
+    fn checkpoint(&self) -> Vec<u8> {
+        let mut buf = vec![u8::from(self.skip_empty)];
+        buf.extend_from_slice(&self.serialize());
+        buf
+    }
+
+    fn restore(bytes: &[u8]) -> Result<Self, DataSketchesError> {
+        let (skip_empty, rest) = match bytes.split_first() {
+            Some((&0, rest)) => (false, rest),
+            Some((&1, rest)) => (true, rest),
+            _ => {
+                let msg = "keyed counter checkpoint lacks its skip flag".to_owned();
+                return Err(DataSketchesError::InvalidBuffer(msg));
+            }
+        };
+        Ok(Self {
+            skip_empty,
+            ..Self::deserialize(rest)?
+        })
     }
 }
 
+#[derive(Default)]
+pub struct Merger {
+    sketch: CpcUnion,
+}
+
 impl Merger {
     pub fn counter(&self) -> Counter {
         let sketch = self.sketch.sketch();
         Counter { sketch }
     }
+
+    /// Merges many in-memory counters, owned or borrowed, into this one's
+    /// union, without the serialization round trip of `read_line()`.
+    pub fn merge_many<I>(&mut self, counters: I)
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Counter>,
+    {
+        for counter in counters {
+            self.sketch.merge_ref(&counter.borrow().sketch);
+        }
+    }
 }
 
 impl LineReducer for Merger {
@@ -115,6 +318,20 @@ impl LineReducer for Merger {
     }
 }
 
+/// The checkpoint is the union's serialized result, which restores to a
+/// union that merges further counters into the same result.
+impl Checkpointable for Merger {
+    fn checkpoint(&self) -> Vec<u8> {
+        self.sketch.sketch().serialize_to_vec()
+    }
+
+    fn restore(bytes: &[u8]) -> Result<Self, DataSketchesError> {
+        let mut sketch = CpcUnion::new();
+        sketch.merge_serialized(bytes)?;
+        Ok(Self { sketch })
+    }
+}
+
 #[derive(Default)]
 pub struct KeyedMerger {
     sketches: HashMap<Vec<u8>, Merger>,
@@ -140,6 +357,13 @@ impl LineReducer for KeyedMerger {
 }
 
 impl KeyedMerger {
+    /// Creates a keyed merger with room for `n` keys before reallocating.
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            sketches: HashMap::with_capacity(n),
+        }
+    }
+
     /// Returns an iterator over all contained keys and their sketches.
     pub fn state(&self) -> impl Iterator<Item = (&[u8], Counter)> {
         self.sketches
@@ -150,48 +374,94 @@ impl KeyedMerger {
 
 pub struct HeavyHitter {
     sketch: HhSketch,
-    k: u64
+    k: u64,
+    threshold: Option<f64>,
 }
 
-// https://users.rust-lang.org/t/logarithm-of-integers/8506/5
-
-fn log2_floor(x: u64) -> usize {
-    const fn num_bits<T>() -> usize { std::mem::size_of::<T>() * 8 }
-    assert!(x > 0);
-    num_bits::<u64>() - x.leading_zeros() as usize - 1
-}
+pub use crate::wrapper::hh::MAX_LG2_K;
 
 impl HeavyHitter {
 
     /// Creates a new heavy hitter sketch targeting elements in the top-k
-    /// by reserving O(k) space.
-    pub fn new( k: u64) -> Self {
-        let lg2_k_with_room = log2_floor(k as u64).max(1) + 2;
+    /// by reserving O(k) space, sized as by [`HhSketch::for_top_k`].
+    pub fn new(k: u64) -> Self {
+        Self::from_sketch(HhSketch::for_top_k(k), k)
+    }
+
+    /// Creates a new heavy hitter sketch targeting elements in the top-k,
+    /// sized as by [`HhSketch::for_top_k_with_headroom`].
+    pub fn with_headroom(k: u64, extra_lg2: u8) -> Self {
+        Self::from_sketch(HhSketch::for_top_k_with_headroom(k, extra_lg2), k)
+    }
+
+    fn from_sketch(sketch: HhSketch, k: u64) -> Self {
         Self {
-            sketch: HhSketch::new(lg2_k_with_room.try_into().unwrap()),
-            k
+            sketch,
+            k,
+            threshold: None,
         }
     }
+
+    /// Creates a new heavy hitter sketch which, rather than the top-k,
+    /// estimates the elements whose count lower bound exceeds `fraction`
+    /// of the lines seen, see [`HhSketch::estimate_above_threshold`]. It
+    /// is sized as if for the top-k with `k = ceil(1 / fraction)`, since
+    /// no more elements than that can exceed it. `fraction` must be in
+    /// `(0, 1]`.
+    pub fn with_threshold(fraction: f64) -> Self {
+        assert!(
+            fraction > 0.0 && fraction <= 1.0,
+            "fraction {} not in (0, 1]",
+            fraction
+        );
+        let k = (1.0 / fraction).ceil() as u64;
+        Self {
+            threshold: Some(fraction),
+            ..Self::new(k)
+        }
+    }
+
+    /// Returns the log2 of the underlying sketch size.
+    pub fn get_lg2_k(&self) -> u8 {
+        self.sketch.get_lg2_k()
+    }
+
+    /// Returns the number of items the sketch can track at once, see
+    /// [`HhSketch::capacity`].
+    pub fn capacity(&self) -> u32 {
+        self.sketch.capacity()
+    }
     
     /// Serializes to base64 string with no newlines or `=` padding.
     pub fn serialize(&self) -> String {
-        unimplemented!()
+        base64::encode_config(self.checkpoint(), base64::STANDARD_NO_PAD)
     }
 
     /// Deserializes from base64 string with no newlines or `=` padding.
-    pub fn deserialize(_s: &str) -> Result<Self, base64::DecodeError> {
-        unimplemented!()
+    pub fn deserialize(s: &str) -> Result<Self, DataSketchesError> {
+        let bytes = base64::decode_config(s, base64::STANDARD_NO_PAD)
+            .map_err(|e| DataSketchesError::InvalidBuffer(e.to_string()))?;
+        Self::restore(&bytes)
     }
 
     /// Returns pairs (heavy hitter slice, estimate of count size)
     pub fn estimate(&self) -> impl Iterator<Item = (&[u8], u64)> {
-        let mut v = self.sketch.estimate_no_fn();
-        v.sort_by_key(|row| row.ub);
-        v
-            .into_iter()
-            .rev()
-            .take(self.k as usize)
-            .map(|row| (row.key, row.ub))
+        self.estimate_with_bounds().map(|(key, _, ub)| (key, ub))
+    }
+
+    /// Returns triples (heavy hitter slice, lower bound, upper bound) for
+    /// the count size, where [`Self::estimate`] only reports the latter.
+    pub fn estimate_with_bounds(&self) -> impl Iterator<Item = (&[u8], u64, u64)> {
+        let rows = match self.threshold {
+            Some(fraction) => self
+                .sketch
+                .estimate_above_threshold(fraction, HhErrorType::NoFalsePositives),
+            None => {
+                let k = self.k.try_into().unwrap_or(usize::MAX);
+                self.sketch.top_k(k, HhErrorType::NoFalseNegatives)
+            }
+        };
+        rows.into_iter().map(|row| (row.key, row.lb, row.ub))
     }
 }
 
@@ -200,3 +470,431 @@ impl LineReducer for HeavyHitter {
         self.sketch.update(line, 1);
     }
 }
+
+/// The checkpoint holds `k` as a little-endian `u64`, then the threshold
+/// fraction as a little-endian `f64`, zero if there's none, then the
+/// sketch as by [`HhSketch::serialize`].
+impl Checkpointable for HeavyHitter {
+    fn checkpoint(&self) -> Vec<u8> {
+        let mut buf = self.k.to_le_bytes().to_vec();
+        buf.extend_from_slice(&self.threshold.unwrap_or(0.0).to_le_bytes());
+        buf.extend_from_slice(&self.sketch.serialize());
+        buf
+    }
+
+    fn restore(bytes: &[u8]) -> Result<Self, DataSketchesError> {
+        let invalid = |msg: &str| DataSketchesError::InvalidBuffer(msg.to_owned());
+        if bytes.len() < 16 {
+            return Err(invalid("heavy hitter checkpoint truncated"));
+        }
+        let (k, rest) = bytes.split_at(8);
+        let (threshold, rest) = rest.split_at(8);
+        let k = u64::from_le_bytes(k.try_into().expect("8 bytes"));
+        let threshold = f64::from_le_bytes(threshold.try_into().expect("8 bytes"));
+        let threshold = if threshold == 0.0 {
+            None
+        } else if threshold > 0.0 && threshold <= 1.0 {
+            Some(threshold)
+        } else {
+            return Err(invalid("heavy hitter threshold not in (0, 1]"));
+        };
+        Ok(Self {
+            sketch: HhSketch::try_deserialize(rest)?,
+            k,
+            threshold,
+        })
+    }
+}
+
+/// Estimates both the distinct count and the heavy hitters of the lines
+/// it reads, so that both can be found in a single pass.
+pub struct CombinedReducer {
+    counter: Counter,
+    hh: HeavyHitter,
+}
+
+impl CombinedReducer {
+    /// Creates a reducer counting distinct lines and finding the top-k, as
+    /// [`HeavyHitter::new`] would.
+    pub fn new(k: u64) -> Self {
+        Self::with_heavy_hitter(HeavyHitter::new(k))
+    }
+
+    /// Creates a reducer counting distinct lines and updating `hh`, e.g.,
+    /// one from [`HeavyHitter::with_threshold`].
+    pub fn with_heavy_hitter(hh: HeavyHitter) -> Self {
+        Self {
+            counter: Counter::default(),
+            hh,
+        }
+    }
+
+    /// Returns the estimate of distinct lines seen.
+    pub fn estimate(&self) -> f64 {
+        self.counter.estimate()
+    }
+
+    /// Returns the distinct line counter.
+    pub fn counter(&self) -> &Counter {
+        &self.counter
+    }
+
+    /// Returns the heavy hitters sketch, see [`HeavyHitter::estimate`].
+    pub fn heavy_hitters(&self) -> &HeavyHitter {
+        &self.hh
+    }
+}
+
+impl LineReducer for CombinedReducer {
+    fn read_line(&mut self, line: &[u8]) {
+        self.counter.read_line(line);
+        self.hh.read_line(line);
+    }
+}
+
+/// Finds the heavy hitters among the values for each key, where the key is
+/// the first word on each line and the value is the rest of the line, as
+/// in [`KeyedCounter`].
+pub struct KeyedHeavyHitter {
+    sketches: HashMap<Vec<u8>, HeavyHitter>,
+    k: u64,
+    skip_empty: bool,
+}
+
+impl KeyedHeavyHitter {
+    /// Creates a keyed heavy hitter reducer targeting the top-k values for
+    /// each key, each sized as [`HeavyHitter::new`] would.
+    pub fn new(k: u64) -> Self {
+        Self {
+            sketches: HashMap::new(),
+            k,
+            skip_empty: false,
+        }
+    }
+
+    /// Creates a keyed heavy hitter reducer as in [`Self::new`] which
+    /// ignores lines with an empty value, as [`KeyedCounter::skipping_empty`]
+    /// does.
+    pub fn skipping_empty(k: u64) -> Self {
+        Self {
+            skip_empty: true,
+            ..Self::new(k)
+        }
+    }
+
+    /// Returns an iterator over all contained keys and their sketches.
+    pub fn state(&self) -> impl Iterator<Item = (&[u8], &HeavyHitter)> {
+        self.sketches.iter().map(|(key, hh)| (key.as_ref(), hh))
+    }
+}
+
+impl LineReducer for KeyedHeavyHitter {
+    fn read_line(&mut self, line: &[u8]) {
+        let space_ix = memchr::memchr(b' ', line).unwrap_or_else(|| {
+            panic!(
+                "line missing space: '{}'",
+                str::from_utf8(line).unwrap_or("BAD UTF-8")
+            )
+        });
+        let (key, value) = (&line[0..space_ix], &line[space_ix + 1..]);
+        if self.skip_empty && value.is_empty() {
+            return;
+        }
+        if !self.sketches.contains_key(key) {
+            self.sketches.insert(key.to_owned(), HeavyHitter::new(self.k));
+        }
+        self.sketches
+            .get_mut(key)
+            .expect("key present")
+            .read_line(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream_reducer::{reduce_stream, reduce_stream_checkpointed};
+
+    use super::*;
+
+    fn counter_over(lines: impl Iterator<Item = u64>) -> Counter {
+        let mut ctr = Counter::default();
+        for line in lines {
+            ctr.read_line(line.to_string().as_bytes());
+        }
+        ctr
+    }
+
+    #[test]
+    fn merge_in_memory() {
+        let a = counter_over(0..1000);
+        let b = counter_over(500..2000);
+
+        let mut merger = Merger::default();
+        merger.read_line(a.serialize().as_bytes());
+        merger.read_line(b.serialize().as_bytes());
+        let expected = merger.counter().estimate();
+
+        let mut merged = a.clone();
+        merged.merge(&b);
+        assert_eq!(merged.estimate(), expected);
+        assert!((1900.0..2100.0).contains(&merged.estimate()));
+        assert_eq!(a.estimate(), counter_over(0..1000).estimate());
+    }
+
+    #[test]
+    fn keyed_capacity() {
+        let lines: Vec<String> = (0..10000)
+            .map(|i| format!("{} {}", i % 100, i))
+            .collect();
+        let mut default = KeyedCounter::default();
+        let mut sized = KeyedCounter::with_capacity(100);
+        let mut reserved = KeyedCounter::skipping_empty();
+        reserved.reserve(10);
+        for line in &lines {
+            default.read_line(line.as_bytes());
+            sized.read_line(line.as_bytes());
+            reserved.read_line(line.as_bytes());
+        }
+        let estimates = |ctr: &KeyedCounter| {
+            let mut estimates: Vec<_> = ctr
+                .state()
+                .map(|(key, ctr)| (key.to_owned(), ctr.estimate()))
+                .collect();
+            estimates.sort_by(|a, b| a.0.cmp(&b.0));
+            estimates
+        };
+        assert_eq!(estimates(&default).len(), 100);
+        assert_eq!(estimates(&default), estimates(&sized));
+        assert_eq!(estimates(&default), estimates(&reserved));
+    }
+
+    #[test]
+    fn keyed_top_keys() {
+        let mut keyed = KeyedCounter::default();
+        // key i has i distinct values, and keys 3 and 13 tie
+        for (key, n) in (1..=10).map(|i| (i, i)).chain(Some((13, 3))) {
+            for value in 0..n {
+                keyed.read_line(format!("{} {}", key, value).as_bytes());
+            }
+        }
+        let top = keyed.top_keys(3);
+        let expected: Vec<(Vec<u8>, f64)> = vec![
+            (b"10".to_vec(), keyed.get(b"10").unwrap().estimate()),
+            (b"9".to_vec(), keyed.get(b"9").unwrap().estimate()),
+            (b"8".to_vec(), keyed.get(b"8").unwrap().estimate()),
+        ];
+        assert_eq!(top, expected);
+
+        let mut all: Vec<_> = keyed
+            .state()
+            .map(|(key, ctr)| (key.to_vec(), ctr.estimate()))
+            .collect();
+        all.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        assert_eq!(keyed.top_keys(100), all);
+        let tied: Vec<&[u8]> = all[7..9].iter().map(|(key, _)| &key[..]).collect();
+        assert_eq!(tied, [&b"13"[..], &b"3"[..]]);
+        assert!(keyed.top_keys(0).is_empty());
+        assert!(keyed.get(b"11").is_none());
+    }
+
+    #[test]
+    fn keyed_len() {
+        let mut keyed = KeyedCounter::skipping_empty();
+        assert!(keyed.is_empty());
+        for line in &["a 1", "b 1", "a 2", "c ", "d x y", "b 3"] {
+            keyed.read_line(line.as_bytes());
+        }
+        assert_eq!(keyed.len(), 3);
+        assert!(!keyed.is_empty());
+        assert_eq!(keyed.len(), keyed.state().count());
+    }
+
+    #[test]
+    fn keyed_serialize_roundtrip() {
+        let mut keyed = KeyedCounter::default();
+        for i in 0..5000u32 {
+            let key = [(i % 7) as u8, 0xff, b' ', b'\n'];
+            let mut line = key.to_vec();
+            line.push(b' ');
+            line.extend_from_slice(i.to_string().as_bytes());
+            keyed.read_line(&line);
+        }
+        let bytes = keyed.serialize();
+        let copy = KeyedCounter::deserialize(&bytes).unwrap();
+        let estimates = |ctr: &KeyedCounter| {
+            let mut estimates: Vec<_> = ctr
+                .state()
+                .map(|(key, ctr)| (key.to_owned(), ctr.estimate()))
+                .collect();
+            estimates.sort_by(|a, b| a.0.cmp(&b.0));
+            estimates
+        };
+        assert_eq!(estimates(&copy).len(), 7);
+        assert_eq!(estimates(&keyed), estimates(&copy));
+        assert_eq!(copy.serialize(), bytes);
+
+        assert!(KeyedCounter::deserialize(&[]).unwrap().is_empty());
+        for len in &[3, 8, bytes.len() - 1] {
+            assert!(KeyedCounter::deserialize(&bytes[..*len]).is_err(), "{}", len);
+        }
+    }
+
+    #[test]
+    fn heavy_hitter_sizing() {
+        assert_eq!(HeavyHitter::new(1).get_lg2_k(), 3);
+        assert_eq!(HeavyHitter::new(10).get_lg2_k(), 5);
+        assert_eq!(HeavyHitter::new(1_000_000).get_lg2_k(), MAX_LG2_K);
+        assert_eq!(HeavyHitter::with_headroom(10, 4).get_lg2_k(), 7);
+        assert_eq!(HeavyHitter::with_headroom(1, 0).get_lg2_k(), 3);
+        assert_eq!(HeavyHitter::with_headroom(u64::MAX, 255).get_lg2_k(), MAX_LG2_K);
+    }
+
+    #[test]
+    fn sizing_matches_library() {
+        assert_eq!(HhSketch::for_top_k(1000).get_lg2_k(), 11);
+        for k in [1, 10, 1000, 1_000_000].iter().cloned() {
+            assert_eq!(HhSketch::for_top_k(k).get_lg2_k(), HeavyHitter::new(k).get_lg2_k());
+            assert_eq!(
+                HhSketch::for_top_k_with_headroom(k, 4).get_lg2_k(),
+                HeavyHitter::with_headroom(k, 4).get_lg2_k()
+            );
+        }
+    }
+
+    #[test]
+    fn heavy_hitter_bounds() {
+        let mut hh = HeavyHitter::new(2);
+        for i in 0..1000u64 {
+            hh.read_line(b"a");
+            hh.read_line(i.to_string().as_bytes());
+        }
+        let with_bounds: Vec<_> = hh.estimate_with_bounds().collect();
+        let ubs: Vec<_> = hh.estimate().collect();
+        assert_eq!(with_bounds.len(), ubs.len());
+        for ((key, lb, ub), (ub_key, ub_only)) in with_bounds.into_iter().zip(ubs) {
+            assert_eq!((key, ub), (ub_key, ub_only));
+            assert!(lb <= ub);
+        }
+        let (key, lb, ub) = hh.estimate_with_bounds().next().unwrap();
+        assert_eq!(key, b"a");
+        assert!(lb <= 1000 && 1000 <= ub, "{} {}", lb, ub);
+    }
+
+    #[test]
+    fn combined_matches_separate() {
+        let mut combined = CombinedReducer::new(2);
+        let mut ctr = Counter::default();
+        let mut hh = HeavyHitter::new(2);
+        for i in 0..3000u64 {
+            let line = (i % 1000 * (i % 3)).to_string();
+            combined.read_line(line.as_bytes());
+            ctr.read_line(line.as_bytes());
+            hh.read_line(line.as_bytes());
+        }
+        assert_eq!(combined.estimate(), ctr.estimate());
+        assert_eq!(combined.counter().serialize(), ctr.serialize());
+        let top: Vec<_> = combined.heavy_hitters().estimate_with_bounds().collect();
+        assert_eq!(top, hh.estimate_with_bounds().collect::<Vec<_>>());
+        assert_eq!(top[0].0, b"0");
+    }
+
+    #[test]
+    fn keyed_heavy_hitter() {
+        let mut keyed = KeyedHeavyHitter::skipping_empty(1);
+        for i in 0..100u64 {
+            keyed.read_line(format!("a {}", i % 3).as_bytes());
+            keyed.read_line(format!("b {}", i % 5 % 2).as_bytes());
+            keyed.read_line(b"c ");
+        }
+        let mut top: Vec<_> = keyed
+            .state()
+            .map(|(key, hh)| (key.to_owned(), hh.estimate().collect::<Vec<_>>()))
+            .map(|(key, est)| (key, est[0].0.to_owned(), est.len()))
+            .collect();
+        top.sort();
+        assert_eq!(
+            top,
+            vec![
+                (b"a".to_vec(), b"0".to_vec(), 1),
+                (b"b".to_vec(), b"0".to_vec(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_many() {
+        let counters: Vec<_> = (0..100u64)
+            .map(|i| counter_over(i * 100..i * 100 + 150))
+            .collect();
+
+        let mut merger = Merger::default();
+        merger.merge_many(&counters);
+        let estimate = merger.counter().estimate();
+        let mut owned = Merger::default();
+        owned.merge_many(counters.clone());
+        assert_eq!(owned.counter().estimate(), estimate);
+
+        let mut sequential = Counter::default();
+        for c in &counters {
+            sequential.merge(c);
+        }
+        assert_eq!(estimate, sequential.estimate());
+
+        let single = counter_over(0..99 * 100 + 150);
+        let (lb, ub) = (single.lower_bound(), single.upper_bound());
+        assert!((lb..=ub).contains(&estimate), "{} not in [{}, {}]", estimate, lb, ub);
+    }
+
+    /// Reduces `input` uninterrupted, and again by restoring a checkpoint
+    /// from partway through and reading the rest, returning both results.
+    fn resume_from_checkpoint<T, F>(new: F, input: &[u8]) -> (T, T)
+    where
+        T: LineReducer + Checkpointable,
+        F: Fn() -> T,
+    {
+        let mut checkpoints = Vec::new();
+        let whole = reduce_stream_checkpointed(input, new(), 700, |bytes, lines| {
+            checkpoints.push((bytes, lines))
+        })
+        .unwrap();
+        assert_eq!(checkpoints.len(), 4);
+
+        let (bytes, lines) = &checkpoints[1];
+        let resumed = T::restore(bytes).unwrap();
+        let rest = input.split(|c| *c == b'\n').skip(*lines as usize);
+        let rest = rest.collect::<Vec<_>>().join(&b'\n');
+        let resumed = reduce_stream(&rest[..], resumed).unwrap();
+        assert!(T::restore(&bytes[..bytes.len() - 1]).is_err());
+        (whole, resumed)
+    }
+
+    #[test]
+    fn resumes_from_checkpoint() {
+        let lines: String = (0..3000u64)
+            .map(|i| format!("{} {}\n", i % 3, i * i % 1009))
+            .collect();
+        let input = lines.as_bytes();
+
+        let (whole, resumed) = resume_from_checkpoint(Counter::default, input);
+        assert_eq!(resumed.serialize(), whole.serialize());
+
+        let (whole, resumed) = resume_from_checkpoint(KeyedCounter::skipping_empty, input);
+        assert_eq!(resumed.checkpoint(), whole.checkpoint());
+        assert_eq!(resumed.len(), 3);
+
+        let (whole, resumed) = resume_from_checkpoint(|| HeavyHitter::new(4), input);
+        assert_eq!(resumed.checkpoint(), whole.checkpoint());
+        let top: Vec<_> = whole.estimate_with_bounds().collect();
+        assert_eq!(resumed.estimate_with_bounds().collect::<Vec<_>>(), top);
+        let (whole, resumed) = resume_from_checkpoint(|| HeavyHitter::with_threshold(0.3), input);
+        assert_eq!(resumed.serialize(), whole.serialize());
+        let copy = HeavyHitter::deserialize(&whole.serialize()).unwrap();
+        assert_eq!(copy.checkpoint(), whole.checkpoint());
+
+        let counters: String = (0..3000u64)
+            .map(|i| counter_over(i..i + 10).serialize() + "\n")
+            .collect();
+        let (whole, resumed) = resume_from_checkpoint(Merger::default, counters.as_bytes());
+        assert_eq!(resumed.counter().serialize(), whole.counter().serialize());
+    }
+}
@@ -0,0 +1,68 @@
+//! Error type for fallible sketch operations.
+
+use std::error::Error;
+use std::fmt;
+
+/// An error raised while operating on a sketch, e.g., when reading a
+/// malformed serialized buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DataSketchesError {
+    /// The underlying datasketches-cpp library threw an exception with the
+    /// given message.
+    CppException(String),
+    /// A sketch's C++ state and its Rust-side bookkeeping disagree, which
+    /// indicates a bug in this crate; see [`crate::HhSketch::check_consistency`].
+    Desync {
+        /// How many keys the C++ side evicted that the Rust side didn't hold.
+        missing_removals: u64,
+    },
+    /// Adding `weight` to a sketch would overflow its `total_weight`.
+    WeightOverflow { total_weight: u64, weight: u64 },
+    /// A serialized sketch buffer was rejected for the given reason before
+    /// being handed to datasketches-cpp.
+    InvalidBuffer(String),
+    /// A serialized sketch buffer claims a serialization version newer than
+    /// the vendored datasketches-cpp supports.
+    UnsupportedVersion {
+        /// Which kind of sketch was being read, e.g., `"cpc"`.
+        family: &'static str,
+        /// The serial version byte from the buffer's preamble.
+        version: u8,
+    },
+}
+
+impl fmt::Display for DataSketchesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CppException(msg) => write!(f, "datasketches-cpp error: {}", msg),
+            Self::Desync { missing_removals } => write!(
+                f,
+                "sketch state desync: {} evicted keys missing from intern",
+                missing_removals
+            ),
+            Self::WeightOverflow {
+                total_weight,
+                weight,
+            } => write!(
+                f,
+                "weight {} would overflow total weight {}",
+                weight, total_weight
+            ),
+            Self::InvalidBuffer(msg) => write!(f, "invalid serialized sketch: {}", msg),
+            Self::UnsupportedVersion { family, version } => write!(
+                f,
+                "unsupported {} serial version {}",
+                family, version
+            ),
+        }
+    }
+}
+
+impl Error for DataSketchesError {}
+
+impl From<cxx::Exception> for DataSketchesError {
+    fn from(e: cxx::Exception) -> Self {
+        Self::CppException(e.what().to_owned())
+    }
+}
@@ -2,13 +2,31 @@
 
 mod bridge;
 pub mod counters;
+mod error;
 pub mod stream_reducer;
 mod wrapper;
 
+pub use error::DataSketchesError;
+pub use wrapper::CpcFlavor;
 pub use wrapper::CpcSketch;
+pub use wrapper::CpcSummary;
 pub use wrapper::CpcUnion;
+pub use wrapper::DecayingCounter;
+pub use wrapper::DisplayKey;
+pub use wrapper::detect_sketch_family;
+pub use wrapper::HhErrorType;
+pub use wrapper::HhRow;
 pub use wrapper::HhSketch;
+pub use wrapper::HhSummary;
+pub use wrapper::round_estimate;
+pub use wrapper::SketchExt;
+pub use wrapper::SketchFamily;
+pub use wrapper::SketchKey;
 pub use wrapper::StaticThetaSketch;
+pub use wrapper::StaticThetaSummary;
+pub use wrapper::TeeCpc;
+pub use wrapper::TeeTheta;
 pub use wrapper::ThetaIntersection;
 pub use wrapper::ThetaSketch;
 pub use wrapper::ThetaUnion;
+pub use wrapper::ThetaWrapped;
@@ -1,19 +1,37 @@
 //! `dsrs` main executable, which provides count-distinct functionality
 //! on the command line.
 
+use std::fs;
+use std::fs::File;
 use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::iter;
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::str;
+use std::str::FromStr;
 
-use dsrs::counters::{Counter, HeavyHitter, KeyedCounter, KeyedMerger, Merger};
-use dsrs::stream_reducer::reduce_stream;
+use dsrs::counters::{
+    CombinedReducer, Counter, HeavyHitter, KeyedCounter, KeyedHeavyHitter, KeyedMerger, Merger,
+};
+use dsrs::stream_reducer::{
+    reduce_stream, reduce_stream_counted, KeyedWords, LineReducer, Progress, SkipEmpty, Words,
+};
+use dsrs::{
+    detect_sketch_family, round_estimate, CpcSketch, DataSketchesError, DisplayKey, HhRow,
+    SketchFamily, StaticThetaSketch, ThetaIntersection, ThetaUnion,
+};
+use flate2::bufread::MultiGzDecoder;
+use structopt::clap::{AppSettings, ArgGroup};
 use structopt::StructOpt;
 
 /// `dsrs` provides both count-distinct and heavy hitter functionality
 /// to the command line.
 ///
 /// `dsrs --hh k` returns the approximate top-k most popular lines from
-/// stdin. It can be viewed as essentially a separate command.
+/// stdin. It can be viewed as essentially a separate command. With
+/// `--key`, it returns the top-k most popular values for each key instead.
 ///
 /// `dsrs [--key] [--raw] [--merge]` returns the count of unique lines
 /// from stdin.
@@ -21,7 +39,8 @@ use structopt::StructOpt;
 /// It has three important options (key, raw, merge), which all interact
 /// and have different I/O expectations.
 ///
-/// No matter what, all input is assumed UTF-8 and the count estimates are rounded.
+/// No matter what, all input is assumed UTF-8 and the count estimates are rounded,
+/// unless `--precision` says otherwise.
 ///
 /// Note newline terminators \r\n and \n are stripped and ignored as far
 /// as unique line content is concerned. So a file with DOS vs UNIX
@@ -44,6 +63,12 @@ use structopt::StructOpt;
 /// if performing multi-level parallel aggregations (a "combiner" in
 /// map reduce literature).
 ///
+/// Each mode is also available as a subcommand which only accepts the
+/// options that apply to it, so that `dsrs count --key --raw` is (3) and
+/// `dsrs merge --key` is (5). Similarly, `dsrs hh k` is `dsrs --hh k` and
+/// `dsrs inspect` is `dsrs --inspect`. Flags for the top-level command
+/// cannot be combined with a subcommand.
+///
 /// There then two main use cases, each of which can be keyed or not.
 ///
 /// # Simple Single-threaded Approximate Count
@@ -88,7 +113,14 @@ use structopt::StructOpt;
 /// # a 3
 /// ```
 #[derive(Debug, StructOpt)]
-#[structopt(name = "dsrs", about = "Approximate count distinct lines.")]
+#[structopt(
+    name = "dsrs",
+    about = "Approximate count distinct lines.",
+    setting = AppSettings::ArgsNegateSubcommands,
+    group = ArgGroup::with_name("hh_mode").conflicts_with_all(&[
+        "raw", "merge", "bounds", "json", "precision", "inspect", "total", "expected-keys", "top",
+    ])
+)]
 struct Opt {
     /// If set, then rather than computing the count of distinct lines
     /// overall, `dsrs` will compute the count of distinct lines for each
@@ -100,7 +132,8 @@ struct Opt {
     /// where stdin-lines would be a table over all input lines where
     /// the `KEY` column is the first word and the rest of the record
     /// is the rest of the line after the first delimiter, which MUST
-    /// be present in every line.
+    /// be present in every line. Keys therefore never contain spaces,
+    /// while the rest of the record may.
     ///
     /// If `--merge` is set, then the value of each key should be a
     /// serialized sketch value resulting from a `dsrs --raw` invocation.
@@ -108,88 +141,846 @@ struct Opt {
     /// This means that the output of `dsrs` is a single line (containing
     /// either the approximate count or sketch, depending on --raw setting)
     /// if --key is unset or a line for each key, prefixed by each key,
-    /// if --key is set.
-    #[structopt(long)]
+    /// if --key is set. Since neither keys nor base64 sketches contain
+    /// spaces, `dsrs --key --raw | dsrs --key --merge` attributes every
+    /// sketch to the same key it was printed with. Keys which aren't valid
+    /// UTF-8 are printed as their bytes in hex, prefixed by `0x`, except
+    /// with `--raw` and without `--json`, where keys are printed as is so
+    /// that `--merge` reads back the exact key.
+    // Conflicts with `--hh-threshold` here, since clap applies conflicts
+    // declared on a group member to every member of its group.
+    #[structopt(long, conflicts_with = "hh-threshold")]
     key: bool,
 
+    /// If set, expects inputs to contain a base64 serialized printout of
+    /// sketches generated by upstream `dsrs --raw` commands. Then `dsrs`
+    /// will merge the deserialized sketches to compute distinct counts
+    /// across all constituent values.
+    #[structopt(long)]
+    merge: bool,
+
+    /// With `--merge`, expect the input to be gzip-compressed, e.g., by
+    /// `dsrs --raw | gzip`. Concatenated gzip streams are read in turn.
+    #[structopt(long, requires = "merge")]
+    gzip: bool,
+
+    #[structopt(flatten)]
+    output: OutputOpt,
+
+    /// If set, rather than counting, read base64 serialized sketches as
+    /// printed by `dsrs --raw` (prefixed by keys if `--key` is set) and
+    /// print a JSON summary of each, plus `key` with `--key`. Each
+    /// summary has a `family` field, `cpc` or `theta`, and `estimate` and
+    /// `serialized_bytes` fields; CPC sketches add `lg_k` and `is_empty`,
+    /// while compact theta sketches add `num_retained`, `theta` and
+    /// `is_ordered`. Lines which aren't such sketches are summarized by an
+    /// `error` field instead, and make `dsrs` exit with an error status
+    /// once all lines are read. Can only be combined with `--key`.
+    #[structopt(
+        long,
+        conflicts_with_all = &[
+            "raw", "merge", "bounds", "json", "precision", "total", "expected-keys", "top",
+        ]
+    )]
+    inspect: bool,
+
+    /// If set, ignore empty lines rather than counting them as a distinct
+    /// (empty) value. With `--key`, lines with an empty value, such as
+    /// `"key "`, are ignored too. Works with `--hh`, but not `--merge`
+    /// or `--inspect`, which expect sketches on every line.
+    #[structopt(long, conflicts_with_all = &["merge", "inspect"])]
+    skip_empty: bool,
+
+    /// If set, read each word of each line, split on Unicode whitespace,
+    /// as its own value, e.g., to count distinct words rather than lines.
+    /// With `--key`, the key is still everything up to the first space,
+    /// and each word after it is read as a value for that key. Works with
+    /// `--hh`, but not `--merge` or `--inspect`. Empty lines, and with
+    /// `--key` keys without words, are ignored.
+    #[structopt(long, conflicts_with_all = &["merge", "inspect"])]
+    words: bool,
+
+    /// If set, also print the total number of lines read after the
+    /// approximate distinct count, or include it as a `total` field with
+    /// `--json`. Empty lines count towards the total even with
+    /// `--skip-empty`. Only applies to unkeyed counting, so it cannot be
+    /// combined with `--key`, `--merge`, or `--raw`.
+    #[structopt(long, conflicts_with_all = &["key", "merge", "raw"])]
+    total: bool,
+
+    /// If set, print the number of lines read so far and the running
+    /// approximate distinct count to stderr every this many lines, e.g.,
+    /// to monitor a long-running pipe. Every input line counts, as with
+    /// `--total`, including lines ignored by `--skip-empty` and however
+    /// many words `--words` reads from each. Stdout is unaffected. Only
+    /// applies to unkeyed counting.
+    #[structopt(long, conflicts_with_all = &["key", "merge", "inspect", "hh_mode"])]
+    progress: Option<NonZeroU64>,
+
+    /// Can only be set if all other flags but `--key` are disabled.
+    /// Returns a upper bound estimate for the number of times a line is
+    /// expected to have appeared, along with the line itself. With `--key`,
+    /// finds the top-k values for each key instead, printing
+    /// `key count value` lines. Lines and keys which aren't valid UTF-8
+    /// are printed as their bytes in hex, prefixed by `0x`.
+    #[structopt(long, group = "hh_mode")]
+    hh: Option<u64>,
+
+    /// Like `--hh`, but print every line whose count lower bound exceeds
+    /// this fraction of all lines, e.g., 0.01 for lines making up more
+    /// than 1% of the input, rather than the top-k. Must be in (0, 1].
+    /// Cannot be combined with `--key`.
+    #[structopt(long, group = "hh_mode", parse(try_from_str = parse_fraction))]
+    hh_threshold: Option<f64>,
+
+    /// With `--key`, the number of distinct keys expected, used to
+    /// allocate room for them up front. This only affects speed and
+    /// memory use, not the output.
+    #[structopt(long, requires = "key")]
+    expected_keys: Option<usize>,
+
+    /// With `--key`, print only the this many keys with the largest
+    /// approximate distinct counts, in descending order of count, with
+    /// ties broken by key, rather than every key in arbitrary order.
+    /// Only applies to counting, not `--merge`, `--inspect`, or `--hh`.
+    #[structopt(long, requires = "key", conflicts_with = "merge")]
+    top: Option<usize>,
+
+    /// With `--hh` or `--hh-threshold`, print a lower and an upper bound on
+    /// the number of times each line appeared, as `lower upper line`,
+    /// rather than just the upper bound.
+    #[structopt(long, requires = "hh_mode")]
+    hh_bounds: bool,
+
+    /// With `--hh` or `--hh-threshold`, first print the approximate number
+    /// of distinct lines, as `dsrs` alone would, reading the input once for
+    /// both. Cannot be combined with `--key`.
+    #[structopt(long, requires = "hh_mode", conflicts_with = "key")]
+    count: bool,
+
+    /// Before reading any input, print the sketch type and size chosen
+    /// to stderr, along with, for `--hh`, the capacity and the count
+    /// above which lines are guaranteed to be found. Stdout is unaffected.
+    #[structopt(long, conflicts_with_all = &["merge", "inspect"])]
+    explain: bool,
+
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+}
+
+/// Options controlling how counts or sketches are printed.
+#[derive(Debug, StructOpt)]
+struct OutputOpt {
     /// If set, the raw flag results in a base64 serialized printout of
     /// the sketch at the end of computation rather than the approximate
     /// distinct count. This is useful when combined with a downstream
     /// `dsrs --merge` operation later to merge multiple sketches.
-    #[structopt(long)]
+    #[structopt(long, conflicts_with_all = &["bounds", "precision"])]
     raw: bool,
 
-    /// If set, expects inputs to contain a base64 serialized printout of
-    /// sketches generated by upstream `dsrs --raw` commands. Then `dsrs`
-    /// will merge the deserialized sketches to compute distinct counts
-    /// across all constituent values.
+    /// If set, print each approximate distinct count as three numbers,
+    /// `lower estimate upper`, where the lower and upper bounds are two
+    /// standard deviations away from the estimate. Cannot be combined
+    /// with `--raw`, which prints sketches rather than counts.
     #[structopt(long)]
-    merge: bool,
+    bounds: bool,
 
-    /// Can only be set if all other flags are disabled. Returns a
-    /// upper bound estimate for the number of times a line is expected
-    /// to have appeared, along with the line itself.
+    /// If set, print one JSON object per output line rather than
+    /// space-delimited text. Objects have an `estimate` field, or a
+    /// `sketch` field with `--raw`, plus a `key` field with `--key`
+    /// and `lower`/`upper` fields with `--bounds`.
     #[structopt(long)]
-    hh: Option<u64>,
+    json: bool,
+
+    /// If set, print approximate counts (including `--bounds`) with
+    /// this many decimal places, or as the full floating point value
+    /// if set to `raw`, rather than rounding to the nearest integer.
+    /// Cannot be combined with `--raw`.
+    #[structopt(long)]
+    precision: Option<Precision>,
+}
+
+/// The modes `dsrs` runs in, selected either explicitly or from the flags
+/// given to the top-level command.
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Count distinct lines, as `dsrs` does without `--merge`, `--hh`,
+    /// or `--inspect`.
+    Count(CountOpt),
+    /// Merge serialized sketches from `dsrs --raw`, as `dsrs --merge` does.
+    Merge(MergeOpt),
+    /// Print the approximate top-k most popular lines, as `dsrs --hh k` does.
+    Hh(HhOpt),
+    /// Print a JSON summary of serialized sketches, as `dsrs --inspect` does.
+    Inspect(InspectOpt),
+    /// Estimate the size of the difference `A \\ B` of two theta sketches.
+    ThetaDiff(ThetaSetOpOpt),
+    /// Estimate the size of the union of two theta sketches.
+    ThetaUnion(ThetaSetOpOpt),
+    /// Estimate the size of the intersection of two theta sketches.
+    ThetaIntersect(ThetaSetOpOpt),
+}
+
+#[derive(Debug, StructOpt)]
+struct CountOpt {
+    /// Count distinct lines for each key, the first word on each line,
+    /// as with `dsrs --key`.
+    #[structopt(long)]
+    key: bool,
+
+    /// The number of distinct keys expected, as with `dsrs --expected-keys`.
+    #[structopt(long, requires = "key")]
+    expected_keys: Option<usize>,
+
+    /// Print only the keys with the largest counts, as with `dsrs --top`.
+    #[structopt(long, requires = "key")]
+    top: Option<usize>,
+
+    /// Ignore empty lines, as with `dsrs --skip-empty`.
+    #[structopt(long)]
+    skip_empty: bool,
+
+    /// Read each word of each line as its own value, as with `dsrs --words`.
+    #[structopt(long)]
+    words: bool,
+
+    /// Also print the total number of lines read, as with `dsrs --total`.
+    #[structopt(long, conflicts_with_all = &["key", "raw"])]
+    total: bool,
+
+    /// Report the running count to stderr every this many lines, as with
+    /// `dsrs --progress`.
+    #[structopt(long, conflicts_with = "key")]
+    progress: Option<NonZeroU64>,
+
+    /// Print the sketch parameters to stderr first, as with `dsrs --explain`.
+    #[structopt(long)]
+    explain: bool,
+
+    #[structopt(flatten)]
+    output: OutputOpt,
+
+    /// Files to read lines from in turn, as if concatenated, rather
+    /// than stdin.
+    #[structopt(parse(from_os_str))]
+    files: Vec<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+struct MergeOpt {
+    /// Merge sketches for each key, as printed by `dsrs count --key --raw`.
+    #[structopt(long)]
+    key: bool,
+
+    /// The number of distinct keys expected, as with `dsrs --expected-keys`.
+    #[structopt(long, requires = "key")]
+    expected_keys: Option<usize>,
+
+    /// Decompress the input with gzip first, as with `dsrs --merge --gzip`.
+    #[structopt(long)]
+    gzip: bool,
+
+    #[structopt(flatten)]
+    output: OutputOpt,
+
+    /// Files to read sketches from in turn, rather than stdin.
+    #[structopt(parse(from_os_str))]
+    files: Vec<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+struct HhOpt {
+    /// The number of most popular lines to print.
+    #[structopt(required_unless = "threshold")]
+    k: Option<u64>,
+
+    /// Print the lines making up more than this fraction of the input
+    /// rather than the top-k, as with `dsrs --hh-threshold`.
+    #[structopt(long, conflicts_with_all = &["k", "key"], parse(try_from_str = parse_fraction))]
+    threshold: Option<f64>,
+
+    /// Print the most popular values for each key, the first word on each
+    /// line, as with `dsrs --key --hh k`.
+    #[structopt(long)]
+    key: bool,
+
+    /// Print bounds on each line's count, as with `dsrs --hh-bounds`.
+    #[structopt(long)]
+    bounds: bool,
+
+    /// First print the approximate number of distinct lines, as with
+    /// `dsrs --hh k --count`.
+    #[structopt(long, conflicts_with = "key")]
+    count: bool,
+
+    /// Print the sketch parameters to stderr first, as with `dsrs --explain`.
+    #[structopt(long)]
+    explain: bool,
+
+    /// Ignore empty lines, as with `dsrs --skip-empty`.
+    #[structopt(long)]
+    skip_empty: bool,
+
+    /// Read each word of each line as its own value, as with `dsrs --words`.
+    #[structopt(long)]
+    words: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct InspectOpt {
+    /// Expect each sketch to be prefixed by a key, as printed by
+    /// `dsrs count --key --raw`.
+    #[structopt(long)]
+    key: bool,
+}
+
+/// Options for the theta set operation subcommands, whose inputs are theta
+/// sketches in their binary serialized form, as written by the library's
+/// `StaticThetaSketch::serialize`.
+#[derive(Debug, StructOpt)]
+struct ThetaSetOpOpt {
+    /// The file holding the first serialized sketch, or `-` for stdin.
+    #[structopt(parse(from_os_str))]
+    a: PathBuf,
+
+    /// The file holding the second serialized sketch, or `-` for stdin.
+    #[structopt(parse(from_os_str))]
+    b: PathBuf,
+
+    /// Write the resulting sketch to stdout in the same binary serialized
+    /// form, rather than printing its rounded estimate.
+    #[structopt(long)]
+    raw: bool,
+}
+
+/// How to print approximate counts, see `--precision`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Precision {
+    Places(usize),
+    Raw,
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "raw" {
+            return Ok(Self::Raw);
+        }
+        s.parse().map(Self::Places).map_err(|_| {
+            format!("expected a number of decimal places or 'raw', got '{}'", s)
+        })
+    }
+}
+
+/// Parses a heavy hitter threshold, which must be in `(0, 1]`.
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.parse() {
+        Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(fraction),
+        _ => Err(format!("expected a fraction in (0, 1], got '{}'", s)),
+    }
+}
+
+impl Opt {
+    /// Returns the explicitly given subcommand, or else the one the
+    /// top-level flags select. Flags which don't apply to it are rejected
+    /// by the parser beforehand.
+    fn into_command(self) -> Command {
+        if let Some(cmd) = self.cmd {
+            return cmd;
+        }
+        let output = self.output;
+
+        if self.hh.is_some() || self.hh_threshold.is_some() {
+            return Command::Hh(HhOpt {
+                k: self.hh,
+                threshold: self.hh_threshold,
+                key: self.key,
+                bounds: self.hh_bounds,
+                words: self.words,
+                count: self.count,
+                explain: self.explain,
+                skip_empty: self.skip_empty,
+            });
+        }
+
+        if self.inspect {
+            return Command::Inspect(InspectOpt { key: self.key });
+        }
+
+        if self.merge {
+            Command::Merge(MergeOpt {
+                key: self.key,
+                expected_keys: self.expected_keys,
+                gzip: self.gzip,
+                output,
+                files: vec![],
+            })
+        } else {
+            Command::Count(CountOpt {
+                key: self.key,
+                expected_keys: self.expected_keys,
+                top: self.top,
+                skip_empty: self.skip_empty,
+                words: self.words,
+                total: self.total,
+                progress: self.progress,
+                explain: self.explain,
+                output,
+                files: vec![],
+            })
+        }
+    }
 }
 
 fn main() {
-    let opt = Opt::from_args();
-
-    if let Some(k) = opt.hh {
-        assert!(!opt.key, "--key and --hh cannot be set simultaneously");
-        assert!(!opt.raw, "--raw and --hh cannot be set simultaneously");
-        assert!(!opt.merge, "--merge and --hh cannot be set simultaneously");
-        if k == 0 {
-            return
-        }
-        let reduced =
-            reduce_stream(io::stdin().lock(), HeavyHitter::new(k)).expect("no io error");
-        for (line, count) in reduced.estimate() {
-            println!("{} {}", count, str::from_utf8(line).expect("valid UTF-8"));
-        }
-        return
-    }
-
-    match (opt.key, opt.merge) {
-        (true, false) => {
-            let reduced =
-                reduce_stream(io::stdin().lock(), KeyedCounter::default()).expect("no io error");
-            print_dict(reduced.state(), opt.raw)
-        }
-        (false, false) => {
-            let reduced =
-                reduce_stream(io::stdin().lock(), Counter::default()).expect("no io error");
-            print_single(&reduced, opt.raw);
-        }
-        (true, true) => {
-            let reduced =
-                reduce_stream(io::stdin().lock(), KeyedMerger::default()).expect("no io error");
-            for (key, ctr) in reduced.state() {
-                print_dict(iter::once((key, &ctr)), opt.raw)
+    match Opt::from_args().into_command() {
+        Command::Count(opt) => count(opt),
+        Command::Merge(opt) => merge(opt),
+        Command::Hh(opt) => heavy_hitters(opt),
+        Command::Inspect(opt) => {
+            let inspector = reduce_stream(io::stdin().lock(), Inspector::new(opt.key))
+                .expect("no io error");
+            if inspector.errors > 0 {
+                process::exit(1);
+            }
+        }
+        Command::ThetaDiff(opt) => theta_set_op(opt, |mut a, b| {
+            a.set_difference(&b);
+            Ok(a)
+        }),
+        Command::ThetaUnion(opt) => theta_set_op(opt, |a, b| {
+            let mut union = ThetaUnion::new();
+            union.try_merge(a)?;
+            union.try_merge(b)?;
+            Ok(union.sketch())
+        }),
+        Command::ThetaIntersect(opt) => theta_set_op(opt, |a, b| {
+            let mut intersection = ThetaIntersection::new();
+            intersection.try_merge(a)?;
+            intersection.try_merge(b)?;
+            Ok(intersection.sketch().expect("sketches merged"))
+        }),
+    }
+}
+
+fn theta_set_op<F>(opt: ThetaSetOpOpt, op: F)
+where
+    F: FnOnce(StaticThetaSketch, StaticThetaSketch) -> Result<StaticThetaSketch, DataSketchesError>,
+{
+    let stdin = Path::new("-");
+    if opt.a == stdin && opt.b == stdin {
+        exit_with_error("only one of the sketches can be read from stdin".to_owned());
+    }
+    let result = op(read_theta(&opt.a), read_theta(&opt.b))
+        .unwrap_or_else(|e| exit_with_error(e.to_string()));
+    if opt.raw {
+        io::stdout()
+            .write_all(&result.serialize_to_vec())
+            .expect("no io error");
+    } else {
+        println!("{}", result.estimate_rounded());
+    }
+}
+
+/// Reads a binary serialized theta sketch from `path`, or stdin if it's `-`.
+fn read_theta(path: &Path) -> StaticThetaSketch {
+    let bytes = if path == Path::new("-") {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes).expect("no io error");
+        bytes
+    } else {
+        fs::read(path).unwrap_or_else(|e| {
+            exit_with_error(format!("could not read {}: {}", path.display(), e))
+        })
+    };
+    // validate up front, which also checks the seed hash
+    StaticThetaSketch::wrap(&bytes)
+        .unwrap_or_else(|e| exit_with_error(format!("{}: {}", path.display(), e)))
+        .to_static()
+}
+
+fn count(opt: CountOpt) {
+    if opt.explain {
+        let per_key = if opt.key { " per key" } else { "" };
+        eprintln!("sketch: cpc{}, lg_k: {}", per_key, CpcSketch::new().get_lg_k());
+    }
+    let split = Split::new(opt.skip_empty, opt.words, opt.key);
+    if opt.key {
+        let mut counter = if opt.skip_empty {
+            KeyedCounter::skipping_empty()
+        } else {
+            KeyedCounter::default()
+        };
+        counter.reserve(opt.expected_keys.unwrap_or(0));
+        let (reduced, _) = reduce_input(counter, &opt.files, split);
+        match opt.top {
+            Some(n) => {
+                let top = reduced.top_keys(n);
+                let counters = top
+                    .iter()
+                    .map(|(key, _)| (&key[..], reduced.get(key).expect("key present")));
+                print_dict(counters, &opt.output)
+            }
+            None => print_dict(reduced.state(), &opt.output),
+        }
+    } else {
+        let (reduced, total) = match opt.progress {
+            Some(every) => {
+                let report = |counter: &Counter, lines| {
+                    let estimate = format_count(counter.estimate(), &opt.output);
+                    eprintln!("{} lines: {}", lines, estimate)
+                };
+                count_with_progress(&opt.files, split, every.get(), report)
+            }
+            None => reduce_input(Counter::default(), &opt.files, split),
+        };
+        print_single(&reduced, Some(total).filter(|_| opt.total), &opt.output);
+    }
+}
+
+fn merge(opt: MergeOpt) {
+    if opt.key {
+        let merger = KeyedMerger::with_capacity(opt.expected_keys.unwrap_or(0));
+        let (reduced, _) = reduce_files(merger, &opt.files, opt.gzip);
+        for (key, ctr) in reduced.state() {
+            print_dict(iter::once((key, &ctr)), &opt.output)
+        }
+    } else {
+        let (reduced, _) = reduce_files(Merger::default(), &opt.files, opt.gzip);
+        print_single(&reduced.counter(), None, &opt.output)
+    }
+}
+
+fn heavy_hitters(opt: HhOpt) {
+    let split = Split::new(opt.skip_empty, opt.words, opt.key);
+    if opt.k == Some(0) {
+        if opt.count {
+            let (reduced, _) = reduce_input(Counter::default(), &[], split);
+            println!("{}", reduced.estimate_rounded());
+        }
+        return;
+    }
+    if opt.explain {
+        let hh = new_heavy_hitter(&opt);
+        let per_key = if opt.key { " per key" } else { "" };
+        eprintln!(
+            "sketch: frequent items{}, lg2_k: {}, capacity: {}",
+            per_key,
+            hh.get_lg2_k(),
+            hh.capacity()
+        );
+        match (opt.k, opt.threshold) {
+            (Some(k), _) => eprintln!("guaranteed to find lines with count above n/{}", k),
+            (None, Some(fraction)) => eprintln!("printing lines with count above {} n", fraction),
+            (None, None) => unreachable!("k or --threshold is required"),
+        }
+        if opt.count {
+            eprintln!("sketch: cpc, lg_k: {}", CpcSketch::new().get_lg_k());
+        }
+    }
+    if opt.key {
+        let k = opt.k.expect("--key conflicts with --threshold");
+        let hh = if opt.skip_empty {
+            KeyedHeavyHitter::skipping_empty(k)
+        } else {
+            KeyedHeavyHitter::new(k)
+        };
+        let (reduced, _) = reduce_input(hh, &[], split);
+        for (key, hh) in reduced.state() {
+            print_heavy_hitters(hh, &format!("{} ", DisplayKey(key)), opt.bounds);
+        }
+    } else {
+        let hh = new_heavy_hitter(&opt);
+        if opt.count {
+            let combined = CombinedReducer::with_heavy_hitter(hh);
+            let (reduced, _) = reduce_input(combined, &[], split);
+            println!("{}", reduced.counter().estimate_rounded());
+            print_heavy_hitters(reduced.heavy_hitters(), "", opt.bounds);
+        } else {
+            let (reduced, _) = reduce_input(hh, &[], split);
+            print_heavy_hitters(&reduced, "", opt.bounds);
+        }
+    }
+}
+
+/// Creates the sketch for the top-k or the threshold `opt` asks for.
+fn new_heavy_hitter(opt: &HhOpt) -> HeavyHitter {
+    match (opt.k, opt.threshold) {
+        (Some(k), _) => HeavyHitter::new(k),
+        (None, Some(fraction)) => HeavyHitter::with_threshold(fraction),
+        (None, None) => unreachable!("k or --threshold is required"),
+    }
+}
+
+/// Prints each heavy hitter line after `prefix`, with its count or bounds.
+fn print_heavy_hitters(hh: &HeavyHitter, prefix: &str, bounds: bool) {
+    for (key, lb, ub) in hh.estimate_with_bounds() {
+        let row = HhRow { key, lb, ub };
+        if bounds {
+            println!("{}{} {} {}", prefix, lb, ub, row.display_key());
+        } else {
+            println!("{}{} {}", prefix, ub, row.display_key());
+        }
+    }
+}
+
+/// How [`reduce_input`] passes the lines it reads on to a reducer.
+#[derive(Clone, Copy, Debug)]
+enum Split {
+    /// Each line, possibly ignoring empty ones, see `--skip-empty`.
+    Lines { skip_empty: bool },
+    /// Each word of each line, see `--words`.
+    Words,
+    /// Each word after each line's key, prefixed by the key, see `--words --key`.
+    KeyedWords,
+}
+
+impl Split {
+    fn new(skip_empty: bool, words: bool, key: bool) -> Self {
+        match (words, key) {
+            (false, _) => Self::Lines { skip_empty },
+            (true, false) => Self::Words,
+            (true, true) => Self::KeyedWords,
+        }
+    }
+}
+
+/// Reduces the lines of each of `files` in turn, or stdin's if there are
+/// none, split as `split` says. Returns the reducer and the number of lines
+/// read (including ignored ones).
+fn reduce_input<T: LineReducer>(reducer: T, files: &[PathBuf], split: Split) -> (T, u64) {
+    match split {
+        Split::Lines { skip_empty: false } => reduce_files(reducer, files, false),
+        Split::Lines { skip_empty: true } => {
+            let (reducer, lines) = reduce_files(SkipEmpty(reducer), files, false);
+            (reducer.0, lines)
+        }
+        Split::Words => {
+            let (reducer, lines) = reduce_files(Words(reducer), files, false);
+            (reducer.0, lines)
+        }
+        Split::KeyedWords => {
+            let (reducer, lines) = reduce_files(KeyedWords::new(reducer), files, false);
+            (reducer.into_inner(), lines)
+        }
+    }
+}
+
+/// Counts distinct values as [`reduce_input`] does, calling `report` with the
+/// counter and the number of input lines read so far every `every` lines.
+/// The lines are counted before `split` is applied, so they match the
+/// returned total.
+fn count_with_progress<F>(
+    files: &[PathBuf],
+    split: Split,
+    every: u64,
+    mut report: F,
+) -> (Counter, u64)
+where
+    F: FnMut(&Counter, u64),
+{
+    fn reduce_with_progress<T, F>(reducer: T, files: &[PathBuf], every: u64, report: F) -> (T, u64)
+    where
+        T: LineReducer,
+        F: FnMut(&T, u64),
+    {
+        let (progress, lines) = reduce_files(Progress::new(reducer, every, report), files, false);
+        (progress.into_inner(), lines)
+    }
+
+    match split {
+        Split::Lines { skip_empty: false } => {
+            reduce_with_progress(Counter::default(), files, every, report)
+        }
+        Split::Lines { skip_empty: true } => {
+            let report = |c: &SkipEmpty<Counter>, lines| report(&c.0, lines);
+            let (reduced, lines) =
+                reduce_with_progress(SkipEmpty(Counter::default()), files, every, report);
+            (reduced.0, lines)
+        }
+        Split::Words => {
+            let report = |c: &Words<Counter>, lines| report(&c.0, lines);
+            let (reduced, lines) =
+                reduce_with_progress(Words(Counter::default()), files, every, report);
+            (reduced.0, lines)
+        }
+        Split::KeyedWords => unreachable!("--progress conflicts with --key"),
+    }
+}
+
+/// Reduces the lines of each of `files` in turn, or stdin's if there are
+/// none, decompressing each with gzip first if `gzip` is set.
+fn reduce_files<T: LineReducer>(reducer: T, files: &[PathBuf], gzip: bool) -> (T, u64) {
+    if files.is_empty() {
+        return reduce_maybe_gzip(io::stdin().lock(), reducer, gzip);
+    }
+    files.iter().fold((reducer, 0), |(reducer, lines), path| {
+        let file = File::open(path).unwrap_or_else(|e| {
+            exit_with_error(format!("could not open {}: {}", path.display(), e))
+        });
+        let (reducer, file_lines) = reduce_maybe_gzip(BufReader::new(file), reducer, gzip);
+        (reducer, lines + file_lines)
+    })
+}
+
+/// Prints `message` to stderr as an error for the user, rather than a panic
+/// for the developer, and exits.
+fn exit_with_error(message: String) -> ! {
+    eprintln!("dsrs: {}", message);
+    process::exit(1)
+}
+
+fn reduce_maybe_gzip<R: BufRead, T: LineReducer>(stream: R, reducer: T, gzip: bool) -> (T, u64) {
+    if gzip {
+        let stream = BufReader::new(MultiGzDecoder::new(stream));
+        reduce_stream_counted(stream, reducer).expect("valid gzip input")
+    } else {
+        reduce_stream_counted(stream, reducer).expect("no io error")
+    }
+}
+
+/// Prints a summary of each serialized sketch line it reads, see `--inspect`.
+struct Inspector {
+    keyed: bool,
+    errors: u64,
+}
+
+impl Inspector {
+    fn new(keyed: bool) -> Self {
+        Self { keyed, errors: 0 }
+    }
+
+    /// Returns the JSON summary of a base64 serialized sketch of any family
+    /// `dsrs` can read.
+    fn summarize(sketch: &str) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+        fn to_object(summary: impl serde::Serialize) -> serde_json::Map<String, serde_json::Value> {
+            match serde_json::to_value(summary).expect("serializable") {
+                serde_json::Value::Object(obj) => obj,
+                _ => unreachable!("summary is a struct"),
             }
         }
-        (false, true) => {
-            let reduced =
-                reduce_stream(io::stdin().lock(), Merger::default()).expect("no io error");
-            print_single(&reduced.counter(), opt.raw)
+
+        let bytes = base64::decode_config(sketch, base64::STANDARD_NO_PAD)
+            .map_err(|e| format!("invalid base64: {}", e))?;
+        let (family, obj) = match detect_sketch_family(&bytes) {
+            Some(SketchFamily::Cpc) => {
+                let sketch = CpcSketch::try_deserialize(&bytes).map_err(|e| e.to_string())?;
+                ("cpc", to_object(sketch.summary()))
+            }
+            Some(SketchFamily::ThetaCompact) => {
+                let sketch =
+                    StaticThetaSketch::try_deserialize(&bytes).map_err(|e| e.to_string())?;
+                let mut obj = to_object(sketch.summary());
+                obj.insert("num_retained".to_owned(), sketch.get_num_retained().into());
+                obj.insert("theta".to_owned(), sketch.get_theta().into());
+                ("theta", obj)
+            }
+            Some(family) => return Err(format!("unsupported sketch family {:?}", family)),
+            None => return Err("unrecognized sketch family".to_owned()),
+        };
+        let mut obj = obj;
+        obj.insert("family".to_owned(), family.into());
+        Ok(obj)
+    }
+}
+
+impl LineReducer for Inspector {
+    fn read_line(&mut self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let (key, sketch) = match line.split_once(' ') {
+            Some((key, sketch)) if self.keyed => (Some(key), Ok(sketch)),
+            None if self.keyed => (None, Err("line missing space".to_owned())),
+            _ => (None, Ok(&line[..])),
+        };
+        let mut obj = sketch.and_then(Self::summarize).unwrap_or_else(|e| {
+            self.errors += 1;
+            let mut obj = serde_json::Map::new();
+            obj.insert("error".to_owned(), e.into());
+            obj
+        });
+        if let Some(key) = key {
+            obj.insert("key".to_owned(), key.into());
         }
+        println!("{}", serde_json::Value::Object(obj));
     }
 }
 
-fn print_dict<'a>(it: impl Iterator<Item = (&'a [u8], &'a Counter)>, raw: bool) {
+fn print_dict<'a>(it: impl Iterator<Item = (&'a [u8], &'a Counter)>, opt: &OutputOpt) {
     for (key, ctr) in it {
-        let as_str = str::from_utf8(key).expect("valid UTF-8");
-        print!("{} ", as_str);
-        print_single(ctr, raw);
+        print_counter(Some(key), ctr, None, opt);
     }
 }
 
-fn print_single(c: &Counter, raw: bool) {
-    if raw {
-        println!("{}", c.serialize());
+fn print_single(c: &Counter, total: Option<u64>, opt: &OutputOpt) {
+    print_counter(None, c, total, opt);
+}
+
+fn print_counter(key: Option<&[u8]>, c: &Counter, total: Option<u64>, opt: &OutputOpt) {
+    if opt.json {
+        let mut obj = serde_json::Map::new();
+        if let Some(key) = key {
+            obj.insert("key".to_owned(), DisplayKey(key).to_string().into());
+        }
+        if opt.raw {
+            obj.insert("sketch".to_owned(), c.serialize().into());
+        } else {
+            obj.insert("estimate".to_owned(), json_count(c.estimate(), opt));
+            if opt.bounds {
+                obj.insert("lower".to_owned(), json_count(c.lower_bound(), opt));
+                obj.insert("upper".to_owned(), json_count(c.upper_bound(), opt));
+            }
+        }
+        if let Some(total) = total {
+            obj.insert("total".to_owned(), total.into());
+        }
+        println!("{}", serde_json::Value::Object(obj));
+        return;
+    }
+    match key {
+        // raw keys are printed as is, so that `--merge` reads back the same key
+        Some(key) if opt.raw => {
+            let mut stdout = io::stdout();
+            stdout.write_all(key).expect("no io error");
+            stdout.write_all(b" ").expect("no io error");
+        }
+        Some(key) => print!("{} ", DisplayKey(key)),
+        None => {}
+    }
+    if opt.raw {
+        print!("{}", c.serialize());
+    } else if opt.bounds {
+        print!(
+            "{} {} {}",
+            format_count(c.lower_bound(), opt),
+            format_count(c.estimate(), opt),
+            format_count(c.upper_bound(), opt)
+        );
     } else {
-        println!("{}", c.estimate().round());
+        print!("{}", format_count(c.estimate(), opt));
+    }
+    if let Some(total) = total {
+        print!(" {}", total);
+    }
+    println!();
+}
+
+fn format_count(x: f64, opt: &OutputOpt) -> String {
+    match opt.precision {
+        None => round_estimate(x).to_string(),
+        Some(Precision::Places(n)) => format!("{:.*}", n, x),
+        Some(Precision::Raw) => x.to_string(),
+    }
+}
+
+fn json_count(x: f64, opt: &OutputOpt) -> serde_json::Value {
+    match opt.precision {
+        None => round_estimate(x).into(),
+        Some(Precision::Places(_)) => format_count(x, opt)
+            .parse::<f64>()
+            .expect("formatted float")
+            .into(),
+        Some(Precision::Raw) => x.into(),
     }
 }
 
@@ -199,7 +990,9 @@ mod tests {
     use std::process;
     use std::str;
 
-    use assert_cmd;
+    use dsrs::counters::Counter;
+    use dsrs::stream_reducer::reduce_stream;
+    use dsrs::StaticThetaSketch;
     use itertools::Itertools;
 
     fn sort_lines(stdout: Vec<u8>) -> Vec<u8> {
@@ -241,7 +1034,7 @@ mod tests {
     /// Asserts that the outputs of dsrs and unix tools when
     /// fed the input from datagen are equal.
     fn validate_equal(datagen: &str, keyed: bool, unix: &str) {
-        let ref args = if keyed { vec!["--key"] } else { vec![] };
+        let args: &[&str] = if keyed { &["--key"] } else { &[] };
         validate_equal_cmd(datagen, args, unix);
         let stdin = eval_bash(datagen);
         let dsrs_stdout = communicate(stdin.clone(), args);
@@ -255,8 +1048,8 @@ mod tests {
             .split(|c| *c == b'\n')
             .enumerate()
             .into_group_map_by(|(i, _)| i % 3)
-            .into_iter()
-            .map(|(_, v)| {
+            .into_values()
+            .map(|v| {
                 v.into_iter()
                     .map(|(_, vv)| vv)
                     .collect::<Vec<_>>()
@@ -277,8 +1070,8 @@ mod tests {
             .split(|c| *c == b'\n')
             .enumerate()
             .into_group_map_by(|(i, _)| (i * 2) / nlines)
-            .into_iter()
-            .map(|(_, v)| {
+            .into_values()
+            .map(|v| {
                 v.into_iter()
                     .map(|(_, vv)| vv)
                     .collect::<Vec<_>>()
@@ -298,7 +1091,7 @@ mod tests {
     fn reduce_with_merge(groups: Vec<Vec<u8>>, keyed: bool) -> Vec<u8> {
         let raw: Vec<_> = groups
             .into_iter()
-            .map(|stdin| {
+            .flat_map(|stdin| {
                 let flags: &[&str] = if keyed {
                     &["--key", "--raw"]
                 } else {
@@ -306,7 +1099,6 @@ mod tests {
                 };
                 communicate(stdin, flags)
             })
-            .flatten()
             .collect();
         let flags: &[&str] = if keyed {
             &["--key", "--merge"]
@@ -317,7 +1109,7 @@ mod tests {
         sort_lines(stdout)
     }
 
-    const UNIX_COUNT_DISTINCT: &'static str = "sort --unique | wc -l";
+    const UNIX_COUNT_DISTINCT: &str = "sort --unique | wc -l";
 
     #[test]
     fn unique_lines() {
@@ -340,7 +1132,7 @@ mod tests {
     }
 
     /// Only works for single-char keys due to -w1, note col order swap.
-    const UNIX_GROUPBY_COUNT_DISTINCT: &'static str =
+    const UNIX_GROUPBY_COUNT_DISTINCT: &str =
         "sort --unique | uniq -w1 -c | awk '{print$2\" \"$1}'";
 
     #[test]
@@ -368,6 +1160,15 @@ mod tests {
         )
     }
 
+    #[test]
+    fn keyed_values_with_spaces() {
+        validate_equal(
+            "echo a b 1; echo a b 2; echo a 1; echo a 1 b; echo b 1; echo b a 1",
+            true,
+            UNIX_GROUPBY_COUNT_DISTINCT,
+        )
+    }
+
     #[test]
     fn keyed_count_empty() {
         validate_equal(
@@ -377,6 +1178,32 @@ mod tests {
         )
     }
 
+    #[test]
+    fn skip_empty() {
+        let datagen = "echo ; echo ; echo 1; echo 2; echo";
+        validate_equal_cmd(datagen, &[], UNIX_COUNT_DISTINCT);
+        validate_equal_cmd(
+            datagen,
+            &["--skip-empty"],
+            &format!("grep -v '^$' | {}", UNIX_COUNT_DISTINCT),
+        );
+
+        let datagen = "echo \"1 \"; echo 1 1; echo 1 3; echo \"2 \"; echo";
+        validate_equal_cmd(
+            datagen,
+            &["--key", "--skip-empty"],
+            &format!("grep -v ' $' | grep -v '^$' | {}", UNIX_GROUPBY_COUNT_DISTINCT),
+        );
+
+        let datagen = "echo ; echo ; echo ; echo 1; echo 1; echo 2";
+        validate_equal_cmd(datagen, &["--hh", "2"], &unix_hh(2));
+        validate_equal_cmd(
+            datagen,
+            &["--hh", "2", "--skip-empty"],
+            &format!("grep -v '^$' | {}", unix_hh(2)),
+        );
+    }
+
     fn validate_equal_cmd(datagen: &str, args: &[&str], unix: &str) {
         let stdin = eval_bash(datagen);
         let dsrs_stdout = communicate(stdin.clone(), args);
@@ -416,4 +1243,803 @@ mod tests {
     fn hh_count_empty() {
         validate_unix_hh("echo ; echo ; echo 1", 1)
     }
+
+    #[test]
+    fn hh_binary_lines() {
+        let mut stdin = b"\xff\xfe\n\xff\xfe\nabc\n".to_vec();
+        let stdout = communicate(stdin.clone(), &["--hh", "2"]);
+        assert_eq!(str::from_utf8(&stdout).unwrap(), "2 0xfffe\n1 abc\n");
+
+        stdin.extend_from_slice(b"k \x80\n");
+        let stdout = communicate(stdin, &["hh", "1", "--bounds"]);
+        assert_eq!(str::from_utf8(&stdout).unwrap(), "2 2 0xfffe\n");
+    }
+
+    #[test]
+    fn binary_keys() {
+        // both keys decode lossily to the same replacement character
+        let stdin = b"\xff a\n\xfe b\n\xfe c\n".to_vec();
+        let stdout = communicate(stdin.clone(), &["--key", "--hh", "1"]);
+        assert_eq!(sort_lines(stdout), b"0xfe 1 b\n0xff 1 a\n".to_vec());
+
+        let stdout = communicate(stdin.clone(), &["--key"]);
+        assert_eq!(sort_lines(stdout), b"0xfe 2\n0xff 1\n".to_vec());
+
+        let mut rows = parse_json(&communicate(stdin, &["--key", "--json"]));
+        rows.sort_by_key(|row| row["key"].as_str().unwrap().to_owned());
+        assert_eq!(
+            rows,
+            vec![
+                serde_json::json!({"key": "0xfe", "estimate": 2}),
+                serde_json::json!({"key": "0xff", "estimate": 1}),
+            ]
+        );
+
+        // a literal `0xff` key displays like the byte 0xff, but raw output
+        // keeps them apart through a merge
+        let stdin = b"\xff a\n0xff b\n0xff c\n".to_vec();
+        let raw = communicate(stdin, &["--key", "--raw"]);
+        let mut keys: Vec<_> = raw
+            .split(|c| *c == b'\n')
+            .filter_map(|line| line.splitn(2, |c| *c == b' ').next())
+            .filter(|key| !key.is_empty())
+            .collect();
+        keys.sort_unstable();
+        assert_eq!(keys, [&b"0xff"[..], b"\xff"]);
+        let stdout = communicate(raw, &["--key", "--merge"]);
+        assert_eq!(sort_lines(stdout), b"0xff 1\n0xff 2\n".to_vec());
+    }
+
+    #[test]
+    fn explain() {
+        let datagen = "seq 100 | sed 's/^/x /'";
+        let stdin = eval_bash(datagen);
+        let unix_top = eval_bash(&format!("({}) | {}", datagen, unix_hh(1000)));
+        for (flags, expected) in [
+            (&["--hh", "1000", "--explain"][..], "lg2_k: 11, capacity: 1536"),
+            (&["hh", "1000", "--explain"], "above n/1000"),
+            (&["--hh-threshold", "0.5", "--explain"], "lg2_k: 3, capacity: 6"),
+            (&["--explain"], "cpc, lg_k: 11"),
+            (&["count", "--key", "--explain"], "cpc per key"),
+        ]
+        .iter()
+        {
+            let out = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))
+                .expect("command created")
+                .args(*flags)
+                .write_stdin(stdin.clone())
+                .assert()
+                .success()
+                .get_output()
+                .clone();
+            let stderr = str::from_utf8(&out.stderr).unwrap();
+            assert!(stderr.contains(expected), "{:?}: {}", flags, stderr);
+            let without: Vec<_> = flags.iter().filter(|f| **f != "--explain").cloned().collect();
+            assert_eq!(out.stdout, communicate(stdin.clone(), &without), "{:?}", flags);
+            if flags.contains(&"1000") {
+                assert_eq!(sort_lines(out.stdout), unix_top);
+            }
+        }
+        assert_usage_error(&["--explain", "--merge"]);
+        assert_usage_error(&["--explain", "--inspect"]);
+    }
+
+    #[test]
+    fn words() {
+        let datagen = "seq 300 | awk '{print $1 % 50, $1 % 7 + 100, \"x\"}'";
+        let unix_words = "tr -s ' ' '\\n'";
+        for flags in [&["--words"][..], &["count", "--words"]].iter() {
+            let unix = format!("{} | {}", unix_words, UNIX_COUNT_DISTINCT);
+            validate_equal_cmd(datagen, flags, &unix);
+        }
+        // word j appears j times, on line j
+        let datagen = "seq 5 | awk '{for (i = 0; i < $1; i++) printf \"%s \", $1; print \"\"}'";
+        let unix = format!("{} | grep . | {}", unix_words, unix_hh(2));
+        validate_equal_cmd(datagen, &["--words", "--hh", "2"], &unix);
+        validate_equal_cmd(datagen, &["hh", "2", "--words"], &unix);
+
+        // with --key, the key stays and each following word is a value
+        let datagen = "seq 200 | awk '{print $1 % 3, $1 % 20, $1 % 30 + 100}'";
+        let unix_keyed = "awk '{print $1, $2; print $1, $3}'";
+        for flags in [&["--key", "--words"][..], &["count", "--key", "--words"]].iter() {
+            let unix = format!("{} | {}", unix_keyed, UNIX_GROUPBY_COUNT_DISTINCT);
+            validate_equal_cmd(datagen, flags, &unix);
+        }
+        // value j appears j times for each key, all on one line
+        let datagen = "for k in a b; do \
+                           seq 4 | awk -v k=$k \
+                               '{printf k; for (i = 0; i < $1; i++) printf \" \" $1; print \"\"}'; \
+                       done";
+        let unix_keyed = "awk '{for (i = 2; i <= NF; i++) print $1, $i}'";
+        validate_equal_cmd(
+            datagen,
+            &["hh", "2", "--key", "--words"],
+            &format!("{} | {}", unix_keyed, unix_keyed_hh(2)),
+        );
+
+        // tabs and runs of spaces separate words too, and empty lines have none
+        let stdout = communicate(b"a\tb  a\n\n  \nc\n".to_vec(), &["--words"]);
+        assert_eq!(stdout, b"3\n");
+
+        assert_usage_error(&["--words", "--merge"]);
+        assert_usage_error(&["--words", "--inspect"]);
+    }
+
+    #[test]
+    fn top_keys() {
+        // key a has 5 distinct values, b and f have 4, and so on
+        let datagen = "i=0; for k in e d c b a; do \
+                           i=$((i+1)); seq $i | sed \"s/^/$k /\"; seq $i | sed \"s/^/$k /\"; \
+                       done; seq 4 | sed 's/^/f /'";
+        let unix = "sort --unique | cut -d' ' -f1 | uniq -c | sort -k1,1nr -k2,2 | head -3 \
+                    | awk '{print $2\" \"$1}'";
+        let stdin = eval_bash(datagen);
+        let expected = eval_bash(&format!("({}) | ({})", datagen, unix));
+        assert_eq!(expected, b"a 5\nb 4\nf 4\n");
+        for flags in [&["--key", "--top", "3"][..], &["count", "--key", "--top", "3"]].iter() {
+            assert_eq!(communicate(stdin.clone(), flags), expected, "{:?}", flags);
+        }
+        let all = communicate(stdin.clone(), &["--key", "--top", "100"]);
+        assert_eq!(sort_lines(all), sort_lines(communicate(stdin, &["--key"])));
+
+        assert_usage_error(&["--top", "3"]);
+        assert_usage_error(&["count", "--top", "3"]);
+        assert_usage_error(&["--key", "--top", "3", "--merge"]);
+        assert_usage_error(&["--key", "--top", "3", "--hh", "2"]);
+    }
+
+    #[test]
+    fn hh_count() {
+        let datagen = "seq 100 | sed 's/$/\\n1\\n2\\n3/'";
+        let stdin = eval_bash(datagen);
+        let distinct = eval_bash(&format!("({}) | {}", datagen, UNIX_COUNT_DISTINCT));
+        let top = eval_bash(&format!("({}) | {}", datagen, unix_hh(3)));
+        for flags in [&["--hh", "3", "--count"][..], &["hh", "3", "--count"]].iter() {
+            let stdout = communicate(stdin.clone(), flags);
+            let split = stdout.iter().position(|c| *c == b'\n').unwrap() + 1;
+            let (count, hh) = stdout.split_at(split);
+            assert_eq!(count, &distinct[..], "{:?}", flags);
+            assert_eq!(sort_lines(hh.to_vec()), top, "{:?}", flags);
+        }
+        let stdout = communicate(stdin, &["hh", "0", "--count"]);
+        assert_eq!(stdout, distinct);
+
+        assert_usage_error(&["--count"]);
+        assert_usage_error(&["hh", "3", "--count", "--key"]);
+    }
+
+    #[test]
+    fn hh_threshold() {
+        let datagen = "seq 1000 | sed 's/$/\\ndominant/'";
+        let stdin = eval_bash(datagen);
+        for flags in [&["--hh-threshold"][..], &["hh", "--threshold"]].iter() {
+            let with = |fraction: &str| {
+                let mut flags = flags.to_vec();
+                flags.push(fraction);
+                communicate(stdin.clone(), &flags)
+            };
+            let stdout = with("0.1");
+            let rows: Vec<_> = str::from_utf8(&stdout).unwrap().lines().collect();
+            assert_eq!(rows.len(), 1, "{:?}", rows);
+            let (count, line) = rows[0].split_once(' ').unwrap();
+            assert_eq!(line, "dominant");
+            assert!(count.parse::<u64>().unwrap() >= 1000, "{}", count);
+            assert!(with("0.9").is_empty());
+        }
+        let stdout = communicate(stdin, &["--hh-threshold", "0.4", "--hh-bounds"]);
+        let bounds = str::from_utf8(&stdout).unwrap().trim_end().to_owned();
+        let fields: Vec<_> = bounds.split(' ').collect();
+        assert_eq!(fields.len(), 3, "{}", bounds);
+        assert_eq!(fields[2], "dominant");
+
+        assert_usage_error(&["--hh-threshold", "0.1", "--hh", "3"]);
+        assert_usage_error(&["--hh-threshold", "0.1", "--key"]);
+        for fraction in &["0", "2", "NaN", "x"] {
+            assert_invalid_value(&["--hh-threshold", fraction]);
+            assert_invalid_value(&["hh", "--threshold", fraction]);
+        }
+        assert_usage_error(&["hh", "3", "--threshold", "0.1"]);
+        assert_usage_error(&["hh", "--threshold", "0.1", "--key"]);
+    }
+
+    /// Per-key top-k of `key value` lines with single-word values, printed
+    /// as `key count value`.
+    fn unix_keyed_hh(k: usize) -> String {
+        format!(
+            "sort | uniq -c | sort -k2,2 -k1,1nr | awk 'n[$2]++ < {}' | \
+             awk '{{print $2, $1, $3}}' | sort",
+            k
+        )
+    }
+
+    #[test]
+    fn keyed_hh() {
+        // value j appears j times for key a, and 7 - j times for key b
+        let datagen = "for j in $(seq 6); do \
+                           seq $j | sed \"s/.*/a $j/\"; \
+                           seq $((7 - j)) | sed \"s/.*/b $j/\"; \
+                       done; echo c 1";
+        for k in 1..4 {
+            let kstr = k.to_string();
+            let unix = unix_keyed_hh(k);
+            validate_equal_cmd(datagen, &["--key", "--hh", &kstr], &unix);
+            validate_equal_cmd(datagen, &["hh", &kstr, "--key"], &unix);
+        }
+
+        let datagen = "echo \"a \"; echo \"a \"; echo a 1; echo b 2";
+        validate_equal_cmd(
+            datagen,
+            &["hh", "1", "--key", "--skip-empty"],
+            &format!("grep -v ' $' | {}", unix_keyed_hh(1)),
+        );
+    }
+
+    /// Parses each output line's trailing `lower estimate upper` triple.
+    fn parse_bounds(stdout: &[u8]) -> Vec<(u64, u64, u64)> {
+        str::from_utf8(stdout)
+            .expect("valid UTF-8")
+            .lines()
+            .map(|line| {
+                let fields: Vec<u64> = line
+                    .split_whitespace()
+                    .rev()
+                    .take(3)
+                    .map(|x| x.parse().expect("integer"))
+                    .collect();
+                assert_eq!(fields.len(), 3, "line {}", line);
+                (fields[2], fields[1], fields[0])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bounds() {
+        let cases: &[(&str, &[&str])] = &[
+            ("seq 10000", &["--bounds"]),
+            ("seq 10000 | sed 's/^/k /'", &["--key", "--bounds"]),
+        ];
+        for (datagen, flags) in cases {
+            let stdout = communicate(eval_bash(datagen), flags);
+            let rows = parse_bounds(&stdout);
+            assert_eq!(rows.len(), 1);
+            let (lower, est, upper) = rows[0];
+            assert!(lower <= est && est <= upper, "{} {} {}", lower, est, upper);
+            assert!(lower < 10000 && 10000 < upper, "{} {}", lower, upper);
+        }
+    }
+
+    fn parse_json(stdout: &[u8]) -> Vec<serde_json::Value> {
+        str::from_utf8(stdout)
+            .expect("valid UTF-8")
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("valid JSON"))
+            .collect()
+    }
+
+    #[test]
+    fn json() {
+        let stdout = communicate(eval_bash("seq 100"), &["--json"]);
+        let rows = parse_json(&stdout);
+        assert_eq!(rows, vec![serde_json::json!({"estimate": 100})]);
+
+        let stdout = communicate(eval_bash("seq 100"), &["--json", "--bounds"]);
+        let rows = parse_json(&stdout);
+        assert_eq!(rows.len(), 1);
+        let obj = rows[0].as_object().expect("object");
+        assert_eq!(obj.len(), 3);
+        let lower = obj["lower"].as_u64().expect("integer");
+        let upper = obj["upper"].as_u64().expect("integer");
+        assert_eq!(obj["estimate"], 100);
+        assert!(lower <= 100 && 100 <= upper);
+    }
+
+    #[test]
+    fn keyed_json() {
+        // keys end at the first space, so quotes, backslashes and tabs
+        // are the characters needing escapes
+        let stdin = b"\"q\\\"\t 1\n\"q\\\"\t 2\nplain 1\n".to_vec();
+        let mut rows = parse_json(&communicate(stdin, &["--key", "--json"]));
+        rows.sort_by_key(|row| row["key"].as_str().expect("string key").to_owned());
+        assert_eq!(
+            rows,
+            vec![
+                serde_json::json!({"key": "\"q\\\"\t", "estimate": 2}),
+                serde_json::json!({"key": "plain", "estimate": 1}),
+            ]
+        );
+    }
+
+    #[test]
+    fn precision() {
+        let stdin = eval_bash("seq 1000");
+        let est = reduce_stream(&stdin[..], Counter::default())
+            .expect("no io error")
+            .estimate();
+        let cases = [
+            ("0", format!("{:.0}", est)),
+            ("3", format!("{:.3}", est)),
+            ("raw", est.to_string()),
+        ];
+        for (precision, expected) in cases.iter() {
+            let stdout = communicate(stdin.clone(), &["--precision", precision]);
+            assert_eq!(str::from_utf8(&stdout).unwrap(), format!("{}\n", expected));
+        }
+
+        let stdout = communicate(eval_bash("seq 3 | sed 's/^/k /'"), &["--key", "--precision", "2"]);
+        assert_eq!(str::from_utf8(&stdout).unwrap(), "k 3.00\n");
+        // small counts are approximate too, which rounding hides
+        let stdout = communicate(eval_bash("seq 3"), &["--precision", "raw"]);
+        assert_eq!(str::from_utf8(&stdout).unwrap(), "3.0005494691861383\n");
+        let stdout = communicate(eval_bash("seq 3"), &[]);
+        assert_eq!(str::from_utf8(&stdout).unwrap(), "3\n");
+
+        // without a precision, ties round to even, as estimate_rounded does
+        let opt = super::OutputOpt { raw: false, bounds: false, json: false, precision: None };
+        for (x, expected) in [(2.5, 2u64), (3.5, 4), (2.4, 2)].iter() {
+            assert_eq!(super::format_count(*x, &opt), expected.to_string());
+            assert_eq!(super::json_count(*x, &opt), *expected);
+        }
+    }
+
+    #[test]
+    fn inspect() {
+        let raw = communicate(eval_bash("seq 1000"), &["--raw"]);
+        let sketch = str::from_utf8(&raw).unwrap().trim_end();
+        let counter = Counter::deserialize(sketch).unwrap();
+        let rows = parse_json(&communicate(raw.clone(), &["--inspect"]));
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["estimate"], counter.estimate());
+        assert_eq!(rows[0]["lg_k"], 11);
+        assert_eq!(rows[0]["is_empty"], false);
+
+        let raw = communicate(eval_bash("seq 10 | sed 's/^/k /'"), &["--key", "--raw"]);
+        let rows = parse_json(&communicate(raw, &["--key", "--inspect"]));
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["key"], "k");
+        assert_eq!(rows[0]["estimate"].as_f64().unwrap().round(), 10.0);
+
+        let mut theta = dsrs::ThetaSketch::new();
+        for key in 0..1000 {
+            theta.update_u64(key);
+        }
+        let bytes = theta.as_static().serialize_to_vec();
+        let line = base64::encode_config(&bytes, base64::STANDARD_NO_PAD) + "\n";
+        let rows = parse_json(&communicate(line.into_bytes(), &["--inspect"]));
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["family"], "theta");
+        assert_eq!(rows[0]["estimate"], 1000.0);
+        assert_eq!(rows[0]["num_retained"], 1000);
+        assert_eq!(rows[0]["theta"], 1.0);
+
+        let mut stdin = communicate(eval_bash("seq 10"), &["--raw"]);
+        stdin.extend_from_slice(b"bm90IGEgc2tldGNo\n"); // "not a sketch"
+        let out = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("command created")
+            .arg("--inspect")
+            .write_stdin(stdin)
+            .assert()
+            .failure()
+            .get_output()
+            .clone();
+        let rows = parse_json(&out.stdout);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["family"], "cpc");
+        assert_eq!(rows[1]["error"], "unrecognized sketch family");
+    }
+
+    #[test]
+    fn total() {
+        let datagen = "seq 100 | xargs -L1 seq";
+        let stdin = eval_bash(datagen);
+        let wc: u64 = str::from_utf8(&eval_bash(&format!("({}) | wc -l", datagen)))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        let est = reduce_stream(&stdin[..], Counter::default())
+            .expect("no io error")
+            .estimate_rounded();
+
+        let stdout = communicate(stdin.clone(), &["--total"]);
+        assert_eq!(
+            str::from_utf8(&stdout).unwrap(),
+            format!("{} {}\n", est, wc)
+        );
+
+        let rows = parse_json(&communicate(stdin, &["--total", "--json"]));
+        assert_eq!(rows, vec![serde_json::json!({"estimate": 100, "total": wc})]);
+
+        let stdout = communicate(eval_bash("echo ; echo 1; echo"), &["--total", "--skip-empty"]);
+        assert_eq!(str::from_utf8(&stdout).unwrap(), "1 3\n");
+    }
+
+    #[test]
+    fn progress() {
+        let stdin = eval_bash("seq 100 && seq 5");
+        for (flags, reports) in [
+            (&["--progress", "10"][..], 10),
+            (&["count", "--progress", "30"], 3),
+            (&["--progress", "200"], 0),
+        ]
+        .iter()
+        {
+            let out = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))
+                .expect("command created")
+                .args(*flags)
+                .write_stdin(stdin.clone())
+                .assert()
+                .success()
+                .get_output()
+                .clone();
+            assert_eq!(str::from_utf8(&out.stdout).unwrap(), "100\n", "{:?}", flags);
+            let stderr = str::from_utf8(&out.stderr).unwrap();
+            assert_eq!(stderr.lines().count(), *reports, "{:?}: {}", flags, stderr);
+        }
+
+        let out = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("command created")
+            .args(["--progress", "50"])
+            .write_stdin(stdin)
+            .assert()
+            .success()
+            .get_output()
+            .clone();
+        assert_eq!(str::from_utf8(&out.stderr).unwrap(), "50 lines: 50\n100 lines: 100\n");
+
+        // progress counts input lines, not the words or values read from them
+        let stdin = eval_bash("seq 0 2 18 | sed 's/.*/& &1/'; echo");
+        for flags in [&["--words"][..], &["--skip-empty"]].iter() {
+            let flags = [*flags, &["--progress", "4", "--total"]].concat();
+            let out = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))
+                .expect("command created")
+                .args(&flags)
+                .write_stdin(stdin.clone())
+                .assert()
+                .success()
+                .get_output()
+                .clone();
+            let stderr = str::from_utf8(&out.stderr).unwrap();
+            let lines: Vec<_> = stderr.lines().map(|l| l.split(':').next().unwrap()).collect();
+            assert_eq!(lines, ["4 lines", "8 lines"], "{:?}: {}", flags, stderr);
+            let total = str::from_utf8(&out.stdout).unwrap().trim_end().split(' ').nth(1);
+            assert_eq!(total, Some("11"), "{:?}", flags);
+        }
+
+        assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("command created")
+            .args(["--progress", "0"])
+            .assert()
+            .failure();
+        assert_usage_error(&["--progress", "10", "--key"]);
+        assert_usage_error(&["--progress", "10", "--merge"]);
+        assert_usage_error(&["count", "--key", "--progress", "10"]);
+    }
+
+    #[test]
+    fn conflicting_flags() {
+        let conflicting = [
+            &["--hh", "3", "--raw"][..],
+            &["--hh", "3", "--merge"],
+            &["--hh", "3", "--bounds"],
+            &["--hh", "3", "--json"],
+            &["--hh", "3", "--precision", "2"],
+            &["--hh", "3", "--inspect"],
+            &["--hh", "3", "--total"],
+            &["--hh", "3", "--key", "--expected-keys", "10"],
+            &["--hh-threshold", "0.1", "--raw"],
+            &["--hh-threshold", "0.1", "--progress", "10"],
+            &["--hh", "3", "--count", "--key"],
+            &["--inspect", "--raw"],
+            &["--inspect", "--merge"],
+            &["--inspect", "--bounds"],
+            &["--inspect", "--json"],
+            &["--inspect", "--precision", "2"],
+            &["--inspect", "--total"],
+            &["--inspect", "--key", "--expected-keys", "10"],
+            &["--inspect", "--key", "--top", "3"],
+            &["--skip-empty", "--merge"],
+            &["--skip-empty", "--inspect"],
+            &["--total", "--key"],
+            &["--total", "--merge"],
+            &["--total", "--raw"],
+        ];
+        for flags in conflicting.iter() {
+            assert_usage_error(flags);
+        }
+    }
+
+    /// Asserts that dsrs rejects a flag's value among `dsrs_flags` while
+    /// parsing them, rather than panicking later.
+    fn assert_invalid_value(dsrs_flags: &[&str]) {
+        let out = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("command created")
+            .args(dsrs_flags)
+            .write_stdin(b"1\n".to_vec())
+            .assert()
+            .code(1)
+            .get_output()
+            .clone();
+        let stderr = str::from_utf8(&out.stderr).expect("valid UTF-8");
+        assert!(stderr.starts_with("error: Invalid value"), "{:?}: {}", dsrs_flags, stderr);
+        assert!(out.stdout.is_empty());
+    }
+
+    /// Asserts that dsrs rejects `dsrs_flags` with a usage error.
+    fn assert_usage_error(dsrs_flags: &[&str]) {
+        let out = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .expect("command created")
+            .args(dsrs_flags)
+            .write_stdin(b"1\n".to_vec())
+            .assert()
+            .failure()
+            .get_output()
+            .clone();
+        let stderr = str::from_utf8(&out.stderr).expect("valid UTF-8");
+        assert!(stderr.starts_with("error: "), "{:?}: {}", dsrs_flags, stderr);
+        assert!(stderr.contains("USAGE:"), "{:?}: {}", dsrs_flags, stderr);
+        assert!(out.stdout.is_empty());
+    }
+
+    #[test]
+    fn subcommands() {
+        let lines = eval_bash("seq 100 | xargs -L1 seq; echo; echo");
+        let keyed = eval_bash("seq 100 | sed 's/^/1 /'; seq 50 | sed 's/^/2 /'");
+        let cases: &[(&[u8], &[&str], &[&str])] = &[
+            (&lines, &[], &["count"]),
+            (&lines, &["--raw"], &["count", "--raw"]),
+            (&lines, &["--bounds", "--json"], &["count", "--bounds", "--json"]),
+            (&lines, &["--skip-empty", "--total"], &["count", "--skip-empty", "--total"]),
+            (&lines, &["--precision", "2"], &["count", "--precision", "2"]),
+            (&lines, &["--hh", "3"], &["hh", "3"]),
+            (&lines, &["--hh", "3", "--skip-empty"], &["hh", "3", "--skip-empty"]),
+            (&keyed, &["--key"], &["count", "--key"]),
+            (&keyed, &["--key", "--raw"], &["count", "--key", "--raw"]),
+            (&keyed, &["--key", "--expected-keys", "2"], &["count", "--key"]),
+            (&keyed, &["--key"], &["count", "--key", "--expected-keys", "1000"]),
+        ];
+        for (stdin, flags, subcommand) in cases {
+            assert_eq!(
+                sort_lines(communicate(stdin.to_vec(), flags)),
+                sort_lines(communicate(stdin.to_vec(), subcommand)),
+                "{:?} vs {:?}",
+                flags,
+                subcommand
+            );
+        }
+
+        let raw = communicate(lines, &["count", "--raw"]);
+        let keyed_raw = communicate(keyed, &["count", "--key", "--raw"]);
+        let cases: &[(&[u8], &[&str], &[&str])] = &[
+            (&raw, &["--merge"], &["merge"]),
+            (&raw, &["--merge", "--raw"], &["merge", "--raw"]),
+            (&raw, &["--merge", "--bounds"], &["merge", "--bounds"]),
+            (&keyed_raw, &["--key", "--merge"], &["merge", "--key"]),
+            (&keyed_raw, &["--key", "--merge"], &["merge", "--key", "--expected-keys", "1"]),
+            (&raw, &["--inspect"], &["inspect"]),
+            (&keyed_raw, &["--key", "--inspect"], &["inspect", "--key"]),
+        ];
+        for (stdin, flags, subcommand) in cases {
+            assert_eq!(
+                sort_lines(communicate(stdin.to_vec(), flags)),
+                sort_lines(communicate(stdin.to_vec(), subcommand)),
+                "{:?} vs {:?}",
+                flags,
+                subcommand
+            );
+        }
+    }
+
+    #[test]
+    fn subcommand_usage_errors() {
+        let invocations: &[&[&str]] = &[
+            &["count", "--raw", "--bounds"],
+            &["count", "--raw", "--precision", "2"],
+            &["count", "--key", "--total"],
+            &["count", "--expected-keys", "3"],
+            &["merge", "--expected-keys", "3"],
+            &["--expected-keys", "3"],
+            &["count", "--raw", "--total"],
+            &["count", "--merge"],
+            &["count", "--hh", "3"],
+            &["merge", "--skip-empty"],
+            &["merge", "--total"],
+            &["hh"],
+            &["hh", "3", "--json"],
+            &["inspect", "--json"],
+            &["inspect", "--raw"],
+            &["--key", "count"],
+            &["quantiles"],
+        ];
+        for flags in invocations {
+            assert_usage_error(flags);
+        }
+    }
+
+    /// Writes `contents` to a file named for the calling test, returning its path.
+    fn temp_file(name: &str, contents: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("dsrs-{}-{}", process::id(), name));
+        std::fs::write(&path, contents).expect("temp file written");
+        path.to_str().expect("UTF-8 temp path").to_owned()
+    }
+
+    #[test]
+    fn file_args() {
+        let a = eval_bash("seq 1000");
+        let b = eval_bash("seq 500 1500; echo");
+        let a_path = temp_file("file_args_a", &a);
+        let b_path = temp_file("file_args_b", &b);
+        let both = [a.clone(), b.clone()].concat();
+
+        for flags in [&["--total"][..], &["--raw"], &["--bounds"], &["--skip-empty"]].iter() {
+            let with_files = [&["count"], *flags, &[&a_path, &b_path]].concat();
+            assert_eq!(
+                communicate(vec![], &with_files),
+                communicate(both.clone(), flags),
+                "{:?}",
+                flags
+            );
+        }
+
+        let a_raw = communicate(a, &["--raw"]);
+        let b_raw = communicate(b, &["--raw"]);
+        let a_raw_path = temp_file("file_args_a_raw", &a_raw);
+        let b_raw_path = temp_file("file_args_b_raw", &b_raw);
+        let both_raw = [a_raw, b_raw].concat();
+        assert_eq!(
+            communicate(vec![], &["merge", &a_raw_path, &b_raw_path]),
+            communicate(both_raw, &["--merge"])
+        );
+
+        for path in &[a_path, b_path, a_raw_path, b_raw_path] {
+            std::fs::remove_file(path).expect("temp file removed");
+        }
+
+        let missing = temp_file("file_args_missing", b"");
+        std::fs::remove_file(&missing).expect("temp file removed");
+        for cmd in &["count", "merge", "theta-union"] {
+            let out = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))
+                .expect("command created")
+                .args([cmd, missing.as_str(), missing.as_str()])
+                .assert()
+                .code(1)
+                .get_output()
+                .clone();
+            let stderr = str::from_utf8(&out.stderr).expect("valid UTF-8");
+            assert!(stderr.starts_with("dsrs: could not "), "{}: {}", cmd, stderr);
+            assert!(stderr.contains(&missing), "{}: {}", cmd, stderr);
+            assert!(!stderr.contains("panicked"), "{}: {}", cmd, stderr);
+            assert!(out.stdout.is_empty());
+        }
+    }
+
+    #[test]
+    fn hh_bounds() {
+        let datagen = "seq 1000 | sed 's/$/\\n1\\n2\\n3/'";
+        let stdin = eval_bash(datagen);
+        let exact = eval_bash(&format!("({}) | {}", datagen, unix_hh(3)));
+        let mut exact: Vec<(u64, &str)> = str::from_utf8(&exact)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let (count, line) = line.split_once(' ').unwrap();
+                (count.parse().unwrap(), line)
+            })
+            .collect();
+        exact.sort_unstable_by_key(|(_, line)| *line);
+        for flags in [&["--hh", "3", "--hh-bounds"][..], &["hh", "3", "--bounds"]].iter() {
+            let stdout = communicate(stdin.clone(), flags);
+            let mut rows: Vec<_> = str::from_utf8(&stdout)
+                .unwrap()
+                .lines()
+                .map(|row| {
+                    let fields: Vec<_> = row.split(' ').collect();
+                    assert_eq!(fields.len(), 3, "{}", row);
+                    let lb: u64 = fields[0].parse().unwrap();
+                    let ub: u64 = fields[1].parse().unwrap();
+                    assert!(lb <= ub, "{}", row);
+                    (fields[2], lb, ub)
+                })
+                .collect();
+            rows.sort_unstable();
+            assert_eq!(rows.len(), exact.len());
+            for ((line, lb, ub), (count, exact_line)) in rows.into_iter().zip(&exact) {
+                assert_eq!(line, *exact_line);
+                assert!(lb <= *count && *count <= ub, "{} {} {}", lb, ub, line);
+            }
+        }
+        assert_usage_error(&["--hh-bounds"]);
+    }
+
+    #[test]
+    fn gzip_merge() {
+        use std::io::Write;
+
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let gzip = |bytes: &[u8]| {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(bytes).unwrap();
+            encoder.finish().unwrap()
+        };
+        let a = communicate(eval_bash("seq 1000"), &["--raw"]);
+        let b = communicate(eval_bash("seq 500 2000"), &["--raw"]);
+        let plain = [a.clone(), b.clone()].concat();
+        let expected = communicate(plain.clone(), &["--merge"]);
+        // one stream, and one per sketch as from `cat a.gz b.gz`
+        for compressed in [gzip(&plain), [gzip(&a), gzip(&b)].concat()].iter() {
+            for flags in [&["--merge", "--gzip"][..], &["merge", "--gzip"]].iter() {
+                assert_eq!(communicate(compressed.clone(), flags), expected);
+            }
+        }
+
+        let a = communicate(eval_bash("seq 10 | sed 's/^/k /'"), &["--key", "--raw"]);
+        let expected = communicate(a.clone(), &["--key", "--merge"]);
+        assert_eq!(communicate(gzip(&a), &["merge", "--key", "--gzip"]), expected);
+
+        assert_usage_error(&["--gzip"]);
+        assert_usage_error(&["count", "--gzip"]);
+    }
+
+    #[test]
+    fn theta_set_ops() {
+        use dsrs::ThetaSketch;
+
+        // small enough that the sketches are exact
+        let sketch_file = |name: &str, keys: std::ops::Range<u64>| {
+            let mut theta = ThetaSketch::new();
+            for key in keys {
+                theta.update_u64(key);
+            }
+            temp_file(name, &theta.as_static().serialize_to_vec())
+        };
+        let a = sketch_file("theta_set_ops_a", 0..1000);
+        let b = sketch_file("theta_set_ops_b", 500..2000);
+        let cases = [
+            ("theta-diff", &a, &b, "500\n"),
+            ("theta-diff", &b, &a, "1000\n"),
+            ("theta-union", &a, &b, "2000\n"),
+            ("theta-intersect", &a, &b, "500\n"),
+        ];
+        for (cmd, x, y, expected) in cases.iter() {
+            let stdout = communicate(vec![], &[cmd, x, y]);
+            assert_eq!(str::from_utf8(&stdout).unwrap(), *expected, "{} {} {}", cmd, x, y);
+        }
+
+        let b_bytes = std::fs::read(&b).unwrap();
+        let stdout = communicate(b_bytes, &["theta-intersect", &a, "-"]);
+        assert_eq!(str::from_utf8(&stdout).unwrap(), "500\n");
+
+        let raw = communicate(vec![], &["theta-union", &a, &b, "--raw"]);
+        assert_eq!(StaticThetaSketch::deserialize(&raw).estimate(), 2000.0);
+        let union = temp_file("theta_set_ops_union", &raw);
+        let stdout = communicate(vec![], &["theta-diff", &union, &a]);
+        assert_eq!(str::from_utf8(&stdout).unwrap(), "1000\n");
+
+        for path in &[a, b, union] {
+            std::fs::remove_file(path).expect("temp file removed");
+        }
+        assert_usage_error(&["theta-diff", "a"]);
+        assert_usage_error(&["theta-union", "a", "b", "--key"]);
+
+        let garbage = temp_file("theta_set_ops_garbage", b"not a theta sketch");
+        let cases = [
+            (&["theta-union", &garbage, &garbage][..], "not a compact theta sketch"),
+            (&["theta-diff", "-", "-"], "only one of the sketches"),
+        ];
+        for (flags, message) in cases.iter() {
+            let out = assert_cmd::Command::cargo_bin(env!("CARGO_PKG_NAME"))
+                .expect("command created")
+                .args(*flags)
+                .assert()
+                .code(1)
+                .get_output()
+                .clone();
+            let stderr = str::from_utf8(&out.stderr).expect("valid UTF-8");
+            assert!(stderr.starts_with("dsrs: "), "{:?}: {}", flags, stderr);
+            assert!(stderr.contains(message), "{:?}: {}", flags, stderr);
+            assert!(out.stdout.is_empty());
+        }
+        std::fs::remove_file(garbage).expect("temp file removed");
+    }
 }
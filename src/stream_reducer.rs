@@ -9,11 +9,124 @@
 use std::io::{BufRead, Error};
 
 use bstr::io::BufReadExt;
+use bstr::ByteSlice;
+
+use crate::DataSketchesError;
 
 pub trait LineReducer {
     fn read_line(&mut self, line: &[u8]);
 }
 
+/// A reducer whose state can be saved partway through a stream and restored
+/// later, e.g., for a long-running job to recover from a crash; see
+/// [`reduce_stream_checkpointed`].
+pub trait Checkpointable: Sized {
+    /// Returns a snapshot of the reducer's current state.
+    fn checkpoint(&self) -> Vec<u8>;
+
+    /// Restores a reducer from a snapshot written by [`Self::checkpoint`],
+    /// which reads further lines just as the original would have.
+    fn restore(bytes: &[u8]) -> Result<Self, DataSketchesError>;
+}
+
+/// Adapts a reducer to ignore empty lines.
+pub struct SkipEmpty<T>(pub T);
+
+impl<T: LineReducer> LineReducer for SkipEmpty<T> {
+    fn read_line(&mut self, line: &[u8]) {
+        if !line.is_empty() {
+            self.0.read_line(line);
+        }
+    }
+}
+
+/// Adapts a reducer to read each word of a line, split on Unicode
+/// whitespace, as its own line. Lines without words are ignored.
+pub struct Words<T>(pub T);
+
+impl<T: LineReducer> LineReducer for Words<T> {
+    fn read_line(&mut self, line: &[u8]) {
+        for word in line.fields() {
+            self.0.read_line(word);
+        }
+    }
+}
+
+/// Like [`Words`], but for keyed reducers, which expect a key up to the
+/// first space of each line: each word after the key is read as the line
+/// `key word`. Lines without a space are passed on as is.
+pub struct KeyedWords<T> {
+    inner: T,
+    buf: Vec<u8>,
+}
+
+impl<T> KeyedWords<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Returns the wrapped reducer.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: LineReducer> LineReducer for KeyedWords<T> {
+    fn read_line(&mut self, line: &[u8]) {
+        let space_ix = match memchr::memchr(b' ', line) {
+            Some(ix) => ix,
+            None => return self.inner.read_line(line),
+        };
+        for word in line[space_ix + 1..].fields() {
+            self.buf.clear();
+            self.buf.extend_from_slice(&line[..=space_ix]);
+            self.buf.extend_from_slice(word);
+            self.inner.read_line(&self.buf);
+        }
+    }
+}
+
+/// Adapts a reducer to call back with its state every so many lines, e.g.,
+/// to report progress on a long-running stream.
+pub struct Progress<T, F> {
+    inner: T,
+    every: u64,
+    lines: u64,
+    callback: F,
+}
+
+impl<T, F: FnMut(&T, u64)> Progress<T, F> {
+    /// Wraps `inner` so that `callback` is called with it and the number of
+    /// lines read so far after every `every` lines. Panics if `every` is 0.
+    pub fn new(inner: T, every: u64, callback: F) -> Self {
+        assert!(every > 0, "progress must be reported every 1 or more lines");
+        Self {
+            inner,
+            every,
+            lines: 0,
+            callback,
+        }
+    }
+
+    /// Returns the wrapped reducer.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: LineReducer, F: FnMut(&T, u64)> LineReducer for Progress<T, F> {
+    fn read_line(&mut self, line: &[u8]) {
+        self.inner.read_line(line);
+        self.lines += 1;
+        if self.lines % self.every == 0 {
+            (self.callback)(&self.inner, self.lines);
+        }
+    }
+}
+
 pub fn reduce_stream<R: BufRead, T: LineReducer>(
     stream: R,
     mut line_reader: T,
@@ -28,10 +141,57 @@ pub fn reduce_stream<R: BufRead, T: LineReducer>(
     Ok(line_reader)
 }
 
+/// Like [`reduce_stream`], but calls `callback` with a fresh checkpoint of the
+/// reducer and the number of lines read so far after every `every` lines. To
+/// resume, [`Checkpointable::restore`] the last checkpoint and reduce the
+/// stream past that many lines. Panics if `every` is 0.
+pub fn reduce_stream_checkpointed<R, T, F>(
+    stream: R,
+    line_reader: T,
+    every: u64,
+    mut callback: F,
+) -> Result<T, Error>
+where
+    R: BufRead,
+    T: LineReducer + Checkpointable,
+    F: FnMut(Vec<u8>, u64),
+{
+    let progress = Progress::new(line_reader, every, |reducer: &T, lines| {
+        callback(reducer.checkpoint(), lines)
+    });
+    Ok(reduce_stream(stream, progress)?.into_inner())
+}
+
+/// Counts the lines passed on to a reducer.
+struct Counted<T> {
+    inner: T,
+    lines: u64,
+}
+
+impl<T: LineReducer> LineReducer for Counted<T> {
+    fn read_line(&mut self, line: &[u8]) {
+        self.lines += 1;
+        self.inner.read_line(line);
+    }
+}
+
+/// Like [`reduce_stream`], but also returns the number of lines read.
+pub fn reduce_stream_counted<R: BufRead, T: LineReducer>(
+    stream: R,
+    line_reader: T,
+) -> Result<(T, u64), Error> {
+    let counted = Counted {
+        inner: line_reader,
+        lines: 0,
+    };
+    let counted = reduce_stream(stream, counted)?;
+    Ok((counted.inner, counted.lines))
+}
+
 #[cfg(test)]
 mod tests {
 
-    use std::u8;
+    use std::iter;
 
     use proptest::{collection, prop_assert_eq, proptest, sample};
 
@@ -49,6 +209,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn reports_progress() {
+        let mut reports = Vec::new();
+        let progress = Progress::new(DumbReducer::default(), 3, |r: &DumbReducer, lines| {
+            reports.push((r.all.len(), lines))
+        });
+        let progress = reduce_stream(&b"a\nb\nc\nd\ne\nf\ng\n"[..], progress).unwrap();
+        assert_eq!(progress.into_inner().all.len(), 14);
+        assert_eq!(reports, vec![(6, 3), (12, 6)]);
+    }
+
+    #[test]
+    fn splits_words() {
+        let input = &b"a  b\tc\n\n \nd\xe2\x80\x83e\n"[..];
+        let words = reduce_stream(input, Words(DumbReducer::default())).unwrap();
+        assert_eq!(words.0.all, b"a\nb\nc\nd\ne\n");
+
+        let input = &b"k a  b\nk\nj \nj\tc d\n"[..];
+        let words = reduce_stream(input, KeyedWords::new(DumbReducer::default())).unwrap();
+        assert_eq!(words.into_inner().all, b"k a\nk b\nk\nj\tc d\n");
+    }
+
     fn non_newlines() -> Vec<u8> {
         (0..u8::MAX).filter(|x| *x != b'\n').collect()
     }
@@ -70,5 +252,19 @@ mod tests {
 
             prop_assert_eq!(reducer.all, file);
         }
+
+        #[test]
+        fn counts_lines(
+            s in collection::vec(collection::vec(sample::select(non_newlines()), 0..81), 0..10)) {
+            let file: Vec<u8> = s
+                .iter()
+                .flat_map(|line| line.iter().chain(iter::once(&b'\n')))
+                .copied()
+                .collect();
+
+            let (_, lines) = reduce_stream_counted(&file[..], DumbReducer::default()).unwrap();
+
+            prop_assert_eq!(lines, s.len() as u64);
+        }
     }
 }
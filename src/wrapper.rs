@@ -5,10 +5,51 @@
 //! lack of inlining, though this may be improved with cross-language
 //! LTO, see dtolnay/cxx#371.
 
+use std::cmp::Ordering;
+
 mod cpc;
+mod decay;
+mod family;
 pub(crate) mod hh;
+mod sketch_key;
+mod tee;
 mod theta;
 
-pub use cpc::{CpcSketch, CpcUnion};
-pub use hh::HhSketch;
-pub use theta::{StaticThetaSketch, ThetaIntersection, ThetaSketch, ThetaUnion};
+/// Rounds a distinct count estimate to the nearest integer, with ties going
+/// to the even one. The cast saturates, so estimates beyond `u64::MAX` give
+/// `u64::MAX`. This is how the sketches' `estimate_rounded` methods round.
+pub fn round_estimate(estimate: f64) -> u64 {
+    // f64::round_ties_even needs a newer rustc than this crate supports
+    let floor = estimate.floor();
+    let rounded = match (estimate - floor).partial_cmp(&0.5) {
+        Some(Ordering::Greater) => floor + 1.0,
+        Some(Ordering::Equal) if floor % 2.0 != 0.0 => floor + 1.0,
+        _ => floor,
+    };
+    rounded as u64
+}
+
+/// Concatenates `keys` into one buffer, along with the end offset of each key
+/// within it, so that a batch crosses the FFI boundary in a single call.
+fn flatten(keys: &[&[u8]]) -> (Vec<u8>, Vec<usize>) {
+    let mut buf = Vec::with_capacity(keys.iter().map(|key| key.len()).sum());
+    let ends = keys
+        .iter()
+        .map(|key| {
+            buf.extend_from_slice(key);
+            buf.len()
+        })
+        .collect();
+    (buf, ends)
+}
+
+pub use cpc::{CpcFlavor, CpcSketch, CpcSummary, CpcUnion};
+pub use decay::DecayingCounter;
+pub use family::{detect_sketch_family, SketchFamily};
+pub use hh::{DisplayKey, HhErrorType, HhRow, HhSketch, HhSummary};
+pub use sketch_key::SketchKey;
+pub use tee::{SketchExt, TeeCpc, TeeTheta};
+pub use theta::{
+    StaticThetaSketch, StaticThetaSummary, ThetaIntersection, ThetaSketch, ThetaUnion,
+    ThetaWrapped,
+};
@@ -1,8 +1,14 @@
 //! Wrapper types for the CPC sketch.
 
-use cxx;
+use serde::Serialize;
+
+use std::convert::TryInto;
 
 use crate::bridge::ffi;
+use crate::wrapper::flatten;
+use crate::wrapper::round_estimate;
+use crate::wrapper::SketchKey;
+use crate::DataSketchesError;
 
 /// The [Compressed Probability Counting][orig-docs] (CPC) sketch is
 /// a dynamically resizing (but still bounded-size) distinct count sketch.
@@ -18,6 +24,9 @@ use crate::bridge::ffi;
 ///
 /// This sketch supports merging through an intermediate type, [`CpcUnion`].
 ///
+/// Values are hashed with a fixed seed and the sketch involves no other
+/// randomness, so the same updates always produce the same sketch.
+///
 /// [orig-docs]: https://datasketches.apache.org/docs/CPC/CPC.html
 /// [hll-wiki]: https://en.wikipedia.org/wiki/HyperLogLog
 /// [benches]: https://datasketches.apache.org/docs/CPC/CpcPerformance.html
@@ -33,26 +42,187 @@ impl CpcSketch {
         }
     }
 
+    /// Build a sketch of `values`, as if by calling
+    /// [`update_u64`](Self::update_u64) on each on a new sketch.
+    pub fn from_u64_slice(values: &[u64]) -> Self {
+        let mut sketch = Self::new();
+        sketch.update_u64_slice(values);
+        sketch
+    }
+
+    /// Build a sketch of `values` by splitting them into `shards` contiguous
+    /// pieces, sketching each piece on the rayon thread pool, and merging the
+    /// results. The estimate is within the usual error of sketching `values`
+    /// sequentially, though the sketch itself may differ.
+    ///
+    /// Requires the `rayon` feature.
+    #[cfg(feature = "rayon")]
+    pub fn par_from_u64(values: &[u64], shards: usize) -> Self {
+        use rayon::prelude::*;
+
+        assert!(shards > 0, "shards must be positive");
+        let shard_len = values.len().div_ceil(shards).max(1);
+        // C++ sketches can't be sent across threads, so shards come back
+        // serialized.
+        let shards: Vec<Vec<u8>> = values
+            .par_chunks(shard_len)
+            .map(|chunk| Self::from_u64_slice(chunk).serialize_to_vec())
+            .collect();
+        let mut union = CpcUnion::new();
+        for shard in &shards {
+            union
+                .merge_serialized(shard)
+                .expect("shard sketches serialize validly");
+        }
+        union.sketch()
+    }
+
     /// Return the current estimate of distinct values seen.
     pub fn estimate(&self) -> f64 {
         self.inner.estimate()
     }
 
+    /// Return the estimate of distinct values seen, rounded half-to-even
+    /// and saturating at `u64::MAX`.
+    pub fn estimate_rounded(&self) -> u64 {
+        round_estimate(self.estimate())
+    }
+
+    /// Return the estimate of distinct values seen across all of `sketches`,
+    /// as if they were merged into a [`CpcUnion`]. The sketches are read in
+    /// place rather than cloned or consumed.
+    pub fn union_estimate(sketches: &[&CpcSketch]) -> f64 {
+        let mut union = CpcUnion::new();
+        for sketch in sketches {
+            union.merge_ref(sketch);
+        }
+        union.estimate()
+    }
+
+    /// Return the log2 of the number of bins in the sketch, which
+    /// determines its accuracy.
+    pub fn get_lg_k(&self) -> u8 {
+        self.inner.get_lg_k()
+    }
+
+    /// Return whether the sketch has seen no values. Unlike the estimate,
+    /// this is exact. There is no counterpart returning the number of
+    /// updates, since distinct counting sketches don't track it.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Return the sketch's key parameters and current state, e.g., for logging.
+    pub fn summary(&self) -> CpcSummary {
+        CpcSummary {
+            lg_k: self.get_lg_k(),
+            estimate: self.estimate(),
+            is_empty: self.is_empty(),
+            serialized_bytes: self.serialize().as_ref().len(),
+        }
+    }
+
+    /// Return an approximate lower bound on the number of distinct values
+    /// seen, `kappa` standard deviations below the estimate. `kappa` must
+    /// be 1, 2, or 3.
+    pub fn get_lower_bound(&self, kappa: u8) -> f64 {
+        assert!((1..=3).contains(&kappa), "kappa {} not in 1..=3", kappa);
+        self.inner.get_lower_bound(kappa)
+    }
+
+    /// Return an approximate upper bound on the number of distinct values
+    /// seen, `kappa` standard deviations above the estimate. `kappa` must
+    /// be 1, 2, or 3.
+    pub fn get_upper_bound(&self, kappa: u8) -> f64 {
+        assert!((1..=3).contains(&kappa), "kappa {} not in 1..=3", kappa);
+        self.inner.get_upper_bound(kappa)
+    }
+
+    /// Return the number of bytes the sketch's variable-size buffers currently
+    /// occupy on the C++ heap. This excludes the fixed-size sketch object itself.
+    pub fn get_allocated_bytes(&self) -> usize {
+        self.inner.get_allocated_bytes()
+    }
+
+    /// Return the sketch's flavor, which indicates how full it is and
+    /// therefore which accuracy regime it's in.
+    pub fn get_flavor(&self) -> CpcFlavor {
+        match self.inner.get_flavor() {
+            0 => CpcFlavor::Empty,
+            1 => CpcFlavor::Sparse,
+            2 => CpcFlavor::Hybrid,
+            3 => CpcFlavor::Pinned,
+            4 => CpcFlavor::Sliding,
+            flavor => unreachable!("unknown CPC flavor {}", flavor),
+        }
+    }
+
     /// Observe a new value. Two values must have the exact same
     /// bytes and lengths to be considered equal.
     pub fn update(&mut self, value: &[u8]) {
         self.inner.pin_mut().update(value)
     }
 
+    /// Observe each of `keys`, equivalent to calling `update()` on each in
+    /// turn, but crossing into C++ once for the whole batch.
+    pub fn update_batch(&mut self, keys: &[&[u8]]) {
+        let (buf, ends) = flatten(keys);
+        self.inner.pin_mut().update_batch(&buf, &ends)
+    }
+
+    /// Apply any buffered updates. Every update method, including the
+    /// batched ones, applies its values to the C++ sketch before returning,
+    /// so this currently does nothing and is never necessary. It marks the
+    /// points where code which feeds the sketch in chunks relies on all
+    /// prior updates being reflected, e.g., by [`Self::estimate`], should
+    /// updates ever be deferred.
+    pub fn flush(&mut self) {}
+
+    /// Observe a new string, equivalent to calling `update()` on its UTF-8 bytes.
+    pub fn update_str(&mut self, value: &str) {
+        self.update(value.as_bytes())
+    }
+
     /// Observe a new `u64`. If the native-endian byte ordered bytes
     /// are equal to any other value seen by `update()`, this will be considered
     /// equal. If you are intending to use serialized sketches across
     /// platforms with different endianness, make sure to convert this
-    /// `value` to network order first.
+    /// `value` to network order first, or use
+    /// [`update_u64_be`](Self::update_u64_be) instead.
     pub fn update_u64(&mut self, value: u64) {
         self.inner.pin_mut().update_u64(value)
     }
 
+    /// Observe each of `values` in turn, as [`update_u64`](Self::update_u64)
+    /// would, but with a single call into C++ for the whole slice.
+    pub fn update_u64_slice(&mut self, values: &[u64]) {
+        self.inner.pin_mut().update_u64_slice(values)
+    }
+
+    /// Observe a new `u64` by its big-endian (network order) bytes, so that
+    /// the resulting sketch is the same on every platform. This is
+    /// equivalent to `update(&value.to_be_bytes())`, and matches
+    /// [`update_u64`](Self::update_u64) only on big-endian platforms.
+    pub fn update_u64_be(&mut self, value: u64) {
+        self.update(&value.to_be_bytes())
+    }
+
+    /// Observe a value which was already hashed to a uniformly distributed
+    /// 64-bit `hash`, e.g., by an upstream system, bypassing this sketch's own
+    /// MurmurHash. Two values are considered equal if their hashes are.
+    ///
+    /// Mixing this with [`Self::update`] or [`Self::update_u64`] on the same
+    /// sketch (or on sketches which are later merged) is nonsensical, since
+    /// the same value would then be counted under two unrelated hashes.
+    pub fn update_prehashed(&mut self, hash: u64) {
+        self.inner.pin_mut().update_prehashed(hash)
+    }
+
+    /// Return the sketch to its empty state, as if newly created.
+    pub fn reset(&mut self) {
+        self.inner.pin_mut().reset()
+    }
+
     pub fn serialize(&self) -> impl AsRef<[u8]> {
         struct UPtrVec(cxx::UniquePtr<cxx::CxxVector<u8>>);
         impl AsRef<[u8]> for UPtrVec {
@@ -63,13 +233,167 @@ impl CpcSketch {
         UPtrVec(self.inner.serialize())
     }
 
-    pub fn deserialize(buf: &[u8]) -> Self {
-        // TODO: this could be friendlier, it currently terminates
-        // the program no bad deserialization, and instead can be a
-        // Result.
+    /// Like [`serialize`](Self::serialize), but copies the bytes into an
+    /// owned `Vec<u8>` that can be stored or sent across threads.
+    pub fn serialize_to_vec(&self) -> Vec<u8> {
+        self.inner.serialize().as_slice().to_vec()
+    }
+
+    /// Like [`serialize_to_vec`](Self::serialize_to_vec), but consumes the
+    /// sketch, freeing it before the bytes are copied out so that it's
+    /// never alive alongside the owned serialization.
+    pub fn into_bytes(self) -> Vec<u8> {
+        let serialized = self.inner.serialize();
+        std::mem::drop(self);
+        serialized.as_slice().to_vec()
+    }
+
+    /// Serialize the sketch so that any two sketches of the same set of values,
+    /// updated in any order, produce the same bytes. The coupons CPC retains
+    /// are order-independent, but its HIP estimator, which
+    /// [`serialize`](Self::serialize) includes, is not, so it's dropped
+    /// here. The result deserializes to a sketch whose estimate comes from
+    /// the coupons alone, as for a [`CpcUnion`]'s result, which is slightly
+    /// less accurate.
+    pub fn serialize_canonical(&self) -> Vec<u8> {
         Self {
-            inner: ffi::deserialize_opaque_cpc_sketch(buf),
+            inner: self.inner.canonical(),
+        }
+        .into_bytes()
+    }
+
+    /// Return a key identifying this sketch by its serialized bytes. CPC
+    /// serialization is always in the compressed form, so sketches built from
+    /// the same updates in the same order have equal keys; for keys that
+    /// ignore the order, see [`serialize_canonical`](Self::serialize_canonical).
+    pub fn canonical_key(&self) -> SketchKey {
+        SketchKey::new(self.serialize_to_vec())
+    }
+
+    /// Deserialize a sketch written by [`Self::serialize`], panicking if
+    /// the buffer is malformed; see [`Self::try_deserialize`].
+    pub fn deserialize<B: AsRef<[u8]>>(buf: B) -> Self {
+        Self::try_deserialize(buf).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Deserialize a sketch written by [`Self::serialize`], returning an
+    /// error if datasketches-cpp rejects the buffer. Buffers from a newer
+    /// serialization format are rejected with
+    /// [`DataSketchesError::UnsupportedVersion`] before reaching C++.
+    pub fn try_deserialize<B: AsRef<[u8]>>(buf: B) -> Result<Self, DataSketchesError> {
+        let buf = buf.as_ref();
+        if let (Some(&version), Some(&Self::FAMILY)) = (buf.get(1), buf.get(2)) {
+            if version > Self::SERIAL_VERSION {
+                return Err(DataSketchesError::UnsupportedVersion {
+                    family: "cpc",
+                    version,
+                });
+            }
         }
+        Ok(Self {
+            inner: ffi::deserialize_opaque_cpc_sketch(buf)?,
+        })
+    }
+
+    /// Read the estimate of a sketch serialized by [`Self::serialize`]
+    /// from its preamble, equal to `deserialize(buf).estimate()` but
+    /// without decompressing the sketch into a new C++ one.
+    ///
+    /// Only the preamble is validated, so unlike [`Self::try_deserialize`],
+    /// a corrupt compressed body after it isn't detected.
+    pub fn estimate_from_bytes(buf: &[u8]) -> Result<f64, DataSketchesError> {
+        let invalid = |msg: String| Err(DataSketchesError::InvalidBuffer(msg));
+        if buf.len() < 8 {
+            return invalid(format!("{} bytes is too short for a cpc sketch", buf.len()));
+        }
+        let (preamble_ints, serial_version, family, lg_k, flags) =
+            (buf[0], buf[1], buf[2], buf[3], buf[5]);
+        if family != Self::FAMILY {
+            return invalid(format!("unexpected family {}", family));
+        }
+        if serial_version > Self::SERIAL_VERSION {
+            return Err(DataSketchesError::UnsupportedVersion {
+                family: "cpc",
+                version: serial_version,
+            });
+        }
+        if serial_version != Self::SERIAL_VERSION {
+            return invalid(format!("unsupported cpc serial version {}", serial_version));
+        }
+        let seed_hash = u16::from_le_bytes([buf[6], buf[7]]);
+        if seed_hash != Self::SEED_HASH {
+            return invalid(format!("unexpected seed hash {:#x}", seed_hash));
+        }
+        if buf.len() < 4 * preamble_ints as usize {
+            return invalid(format!("bad preamble length {}", preamble_ints));
+        }
+        let flag = |bit: u8| flags & (1 << bit) != 0;
+        if !flag(Self::HAS_TABLE_FLAG) && !flag(Self::HAS_WINDOW_FLAG) {
+            return Ok(0.0);
+        }
+        let num_coupons = u32::from_le_bytes([buf[8], buf[9], buf[10], buf[11]]);
+        if !flag(Self::HAS_HIP_FLAG) {
+            // sketches from unions lose their HIP estimate, see `get_estimate`
+            return Ok(ffi::cpc_icon_estimate(lg_k, num_coupons));
+        }
+        // whichever of the table and window are present, the HIP estimate
+        // follows its count in the 4th int and the 8-byte kxp
+        match buf.get(24..32) {
+            Some(hip) => Ok(f64::from_le_bytes(hip.try_into().unwrap())),
+            None => invalid(format!("{} bytes is too short for a hip estimate", buf.len())),
+        }
+    }
+
+    /// The serial version and family, the second and third preamble bytes,
+    /// written by the vendored datasketches-cpp.
+    const SERIAL_VERSION: u8 = 1;
+    const FAMILY: u8 = 16;
+    /// The seed hash of the default seed, which all sketches here use.
+    const SEED_HASH: u16 = 0x93cc;
+    /// Bits of the flags byte, the sixth in the preamble.
+    const HAS_HIP_FLAG: u8 = 2;
+    const HAS_TABLE_FLAG: u8 = 3;
+    const HAS_WINDOW_FLAG: u8 = 4;
+}
+
+/// The internal representation of a [`CpcSketch`], which follows from the
+/// number of coupons `C` it has collected out of its `K = 2^lg_k` bins.
+/// Later flavors compare greater; a sketch only moves to later flavors as
+/// it's updated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CpcFlavor {
+    /// `C = 0`.
+    Empty,
+    /// `1 <= C < 3K/32`, where only a table of coupons is kept.
+    Sparse,
+    /// `3K/32 <= C < K/2`.
+    Hybrid,
+    /// `K/2 <= C < 27K/8`.
+    Pinned,
+    /// `27K/8 <= C`.
+    Sliding,
+}
+
+/// A snapshot of a [`CpcSketch`], see [`CpcSketch::summary`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CpcSummary {
+    pub lg_k: u8,
+    pub estimate: f64,
+    pub is_empty: bool,
+    pub serialized_bytes: usize,
+}
+
+impl Clone for CpcSketch {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl Default for CpcSketch {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -86,8 +410,46 @@ impl CpcUnion {
         }
     }
 
+    /// Merge `sketch` into the union, panicking if datasketches-cpp rejects
+    /// it; see [`Self::try_merge`].
     pub fn merge(&mut self, sketch: CpcSketch) {
-        self.inner.pin_mut().merge(sketch.inner)
+        self.try_merge(sketch).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Merge `sketch` into the union, returning an error if datasketches-cpp
+    /// rejects it, e.g., when the sketch was hashed with a different seed.
+    pub fn try_merge(&mut self, sketch: CpcSketch) -> Result<(), DataSketchesError> {
+        self.inner.pin_mut().merge(sketch.inner)?;
+        Ok(())
+    }
+
+    /// Merge `sketch` into the union in place, like [`Self::merge`] but
+    /// without giving up or copying the sketch.
+    pub fn merge_ref(&mut self, sketch: &CpcSketch) {
+        self.try_merge_ref(sketch).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Merge `sketch` into the union in place, returning an error as
+    /// [`Self::try_merge`] does.
+    pub fn try_merge_ref(&mut self, sketch: &CpcSketch) -> Result<(), DataSketchesError> {
+        self.inner.pin_mut().merge_ref(&sketch.inner)?;
+        Ok(())
+    }
+
+    /// Merge a sketch given in its serialized form, as produced by
+    /// [`CpcSketch::serialize`], without constructing an intermediate
+    /// [`CpcSketch`]. Malformed buffers result in an error, leaving the
+    /// union unchanged.
+    pub fn merge_serialized(&mut self, buf: &[u8]) -> Result<(), DataSketchesError> {
+        self.inner.pin_mut().merge_serialized(buf)?;
+        Ok(())
+    }
+
+    /// Return the estimate of distinct values seen across the merged
+    /// sketches, equal to `self.sketch().estimate()`. This is computed from
+    /// the union's coupon count, without building the unioned sketch.
+    pub fn estimate(&self) -> f64 {
+        self.inner.estimate()
     }
 
     /// Retrieve the current unioned sketch as a copy.
@@ -98,6 +460,12 @@ impl CpcUnion {
     }
 }
 
+impl Default for CpcUnion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use byte_slice_cast::AsByteSlice;
@@ -135,6 +503,241 @@ mod tests {
         }
     }
 
+    #[test]
+    fn bounds_bracket_estimate() {
+        let mut cpc = CpcSketch::new();
+        for key in 0u64..10000 {
+            cpc.update_u64(key);
+        }
+        let est = cpc.estimate();
+        for kappa in 1..=3 {
+            assert!(cpc.get_lower_bound(kappa) <= est);
+            assert!(cpc.get_upper_bound(kappa) >= est);
+        }
+        assert!(cpc.get_lower_bound(3) <= cpc.get_lower_bound(1));
+        assert!(cpc.get_upper_bound(3) >= cpc.get_upper_bound(1));
+    }
+
+    /// SplitMix64, standing in for an upstream hash function.
+    fn mix(key: u64) -> u64 {
+        let mut z = key.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    #[test]
+    fn prehashed() {
+        let n = 100 * 1000;
+        let mut a = CpcSketch::new();
+        let mut b = CpcSketch::new();
+        for key in 0u64..n {
+            a.update_prehashed(mix(key));
+            b.update_prehashed(mix(key));
+            b.update_prehashed(mix(key));
+        }
+        assert_eq!(a.estimate(), b.estimate());
+        assert_eq!(a.serialize().as_ref(), b.serialize().as_ref());
+        check_cycle(&a);
+        let est = a.estimate();
+        let lb = n as f64 * 0.95;
+        let ub = n as f64 * 1.05;
+        assert!((lb..ub).contains(&est));
+    }
+
+    #[test]
+    fn allocated_bytes_grow() {
+        let mut cpc = CpcSketch::new();
+        let mut last = cpc.get_allocated_bytes();
+        for i in 0..4 {
+            for key in 0u64..(10u64.pow(i + 1)) {
+                cpc.update_u64(key);
+            }
+            let now = cpc.get_allocated_bytes();
+            assert!(now > last, "{} -> {} bytes at iteration {}", last, now, i);
+            last = now;
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_from_u64_matches_sequential() {
+        let values: Vec<u64> = (0..100_000).map(|i| i % 40_000).collect();
+        let mut sequential = CpcSketch::new();
+        for &value in &values {
+            sequential.update_u64(value);
+        }
+        for &shards in &[1, 3, 16, 1_000_000] {
+            let par = CpcSketch::par_from_u64(&values, shards);
+            let (par, seq) = (par.estimate(), sequential.estimate());
+            assert!((par - seq).abs() / seq < 0.05, "{} vs {}", par, seq);
+        }
+        assert!(CpcSketch::par_from_u64(&[], 4).is_empty());
+    }
+
+    #[test]
+    fn serialize_to_vec_roundtrip() {
+        let mut cpc = CpcSketch::new();
+        for key in 0u64..10000 {
+            cpc.update_u64(key);
+        }
+        let bytes = cpc.serialize_to_vec();
+        assert_eq!(bytes, cpc.serialize().as_ref());
+        let copy = CpcSketch::deserialize(&bytes);
+        assert_eq!(copy.serialize_to_vec(), bytes);
+        assert_eq!(copy.estimate(), cpc.estimate());
+    }
+
+    #[test]
+    fn into_bytes_roundtrip() {
+        let mut cpc = CpcSketch::new();
+        for key in 0u64..10000 {
+            cpc.update_u64(key);
+        }
+        let bytes = cpc.serialize_to_vec();
+        let estimate = cpc.estimate();
+        let owned = cpc.into_bytes();
+        assert_eq!(owned, bytes);
+        let copy = CpcSketch::deserialize(&owned);
+        assert_eq!(copy.serialize_to_vec(), bytes);
+        assert_eq!(copy.estimate(), estimate);
+    }
+
+    #[test]
+    fn serialize_canonical_ignores_order() {
+        // sizes covering the sparse, hybrid, pinned and sliding flavors
+        for n in [10u64, 1000, 5000, 100_000].iter().cloned() {
+            let forward = CpcSketch::from_u64_slice(&(0..n).collect::<Vec<_>>());
+            let backward = CpcSketch::from_u64_slice(&(0..n).rev().collect::<Vec<_>>());
+            let canonical = forward.serialize_canonical();
+            assert_eq!(canonical, backward.serialize_canonical(), "n = {}", n);
+            assert_eq!(canonical, CpcSketch::deserialize(&canonical).serialize_canonical());
+            let copy = CpcSketch::deserialize(&canonical);
+            assert_eq!(copy.get_flavor(), forward.get_flavor());
+            assert!((copy.estimate() - n as f64).abs() < n as f64 * 0.05);
+        }
+        assert_eq!(
+            CpcSketch::new().serialize_canonical(),
+            CpcSketch::new().serialize_to_vec()
+        );
+    }
+
+    #[test]
+    fn deserialize_owned_buffers() {
+        let mut cpc = CpcSketch::new();
+        cpc.update_u64(1);
+        let bytes = cpc.serialize_to_vec();
+        let slice: &[u8] = &bytes;
+        let boxed: Box<[u8]> = bytes.clone().into_boxed_slice();
+        for copy in [
+            CpcSketch::deserialize(slice),
+            CpcSketch::deserialize(boxed),
+            CpcSketch::deserialize(cpc.serialize()),
+            CpcSketch::deserialize(bytes.clone()),
+        ]
+        .iter()
+        {
+            assert_eq!(copy.serialize_to_vec(), bytes);
+        }
+    }
+
+    #[test]
+    fn update_u64_be_portable() {
+        let mut cpc = CpcSketch::new();
+        let mut bytes = CpcSketch::new();
+        for value in 0u64..4 {
+            cpc.update_u64_be(value);
+            bytes.update(&value.to_be_bytes());
+        }
+        assert_eq!(cpc.serialize_to_vec(), bytes.serialize_to_vec());
+        // identical on every platform, unlike with update_u64
+        let reference: &[u8] = &[
+            8, 1, 16, 11, 0, 14, 204, 147, 4, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 128, 250, 159, 64,
+            180, 0, 5, 38, 64, 1, 16, 64, 201, 98, 185, 170, 135, 201, 1, 0,
+        ];
+        assert_eq!(cpc.serialize_to_vec(), reference);
+    }
+
+    #[test]
+    fn update_batch_matches_update() {
+        let keys: Vec<String> = (0..10000).map(|i| format!("key{}", i % 3000)).collect();
+        let keys: Vec<&[u8]> = keys.iter().map(|key| key.as_bytes()).collect();
+        let mut a = CpcSketch::new();
+        let mut b = CpcSketch::new();
+        for key in &keys {
+            a.update(key);
+        }
+        b.update_batch(&keys[..5000]);
+        b.update_batch(&[]);
+        b.update_batch(&keys[5000..]);
+        b.update_batch(&[b""]);
+        a.update(b"");
+        assert_eq!(a.serialize().as_ref(), b.serialize().as_ref());
+    }
+
+    #[test]
+    fn bulk_u64_matches_update() {
+        let values: Vec<u64> = (0..1_000_000).map(|i| i * 7 % 300_000).collect();
+        let mut looped = CpcSketch::new();
+        for &value in &values {
+            looped.update_u64(value);
+        }
+        let bulk = CpcSketch::from_u64_slice(&values);
+        assert_eq!(bulk.estimate(), looped.estimate());
+        assert_eq!(bulk.serialize().as_ref(), looped.serialize().as_ref());
+        assert!(CpcSketch::from_u64_slice(&[]).is_empty());
+    }
+
+    #[test]
+    fn flavor() {
+        let mut cpc = CpcSketch::new();
+        assert_eq!(cpc.get_flavor(), CpcFlavor::Empty);
+        let mut flavors = vec![cpc.get_flavor()];
+        for key in 0u64..100_000 {
+            cpc.update_u64(key);
+            if cpc.get_flavor() != *flavors.last().unwrap() {
+                flavors.push(cpc.get_flavor());
+            }
+        }
+        use CpcFlavor::*;
+        assert_eq!(flavors, vec![Empty, Sparse, Hybrid, Pinned, Sliding]);
+        cpc.reset();
+        assert_eq!(cpc.get_flavor(), CpcFlavor::Empty);
+    }
+
+    #[test]
+    fn flush_between_batches() {
+        let keys: Vec<String> = (0..20000).map(|i| format!("key{}", i)).collect();
+        let keys: Vec<&[u8]> = keys.iter().map(|key| key.as_bytes()).collect();
+        let mut cpc = CpcSketch::new();
+        let mut last = 0.0;
+        for (i, chunk) in keys.chunks(1000).enumerate() {
+            cpc.update_batch(chunk);
+            cpc.update_u64_slice(&[i as u64]);
+            cpc.flush();
+            let estimate = cpc.estimate();
+            assert!(estimate >= last, "{} after {}", estimate, last);
+            last = estimate;
+        }
+        let mut looped = CpcSketch::new();
+        for (i, chunk) in keys.chunks(1000).enumerate() {
+            for key in chunk {
+                looped.update(key);
+            }
+            looped.update_u64(i as u64);
+        }
+        assert_eq!(cpc.serialize().as_ref(), looped.serialize().as_ref());
+    }
+
+    #[test]
+    fn update_str_matches_bytes() {
+        let mut a = CpcSketch::new();
+        let mut b = CpcSketch::new();
+        a.update_str("hello");
+        b.update("hello".as_bytes());
+        assert_eq!(a.serialize().as_ref(), b.serialize().as_ref());
+    }
+
     #[test]
     fn cpc_empty() {
         let cpc = CpcSketch::new();
@@ -142,6 +745,81 @@ mod tests {
         check_cycle(&cpc);
     }
 
+    #[test]
+    fn is_empty() {
+        let mut cpc = CpcSketch::new();
+        assert!(cpc.is_empty());
+        assert!(CpcSketch::deserialize(cpc.serialize()).is_empty());
+        cpc.update(b"");
+        assert!(!cpc.is_empty());
+        assert!(!CpcSketch::deserialize(cpc.serialize()).is_empty());
+        cpc.reset();
+        assert!(cpc.is_empty());
+    }
+
+    #[test]
+    fn canonical_keys() {
+        let build = |n: u64| {
+            let mut cpc = CpcSketch::new();
+            for key in 0..n {
+                cpc.update_u64(key);
+            }
+            cpc
+        };
+        let a = build(1000).canonical_key();
+        let b = build(1000).canonical_key();
+        let c = build(2000).canonical_key();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        let keys: std::collections::HashSet<_> = vec![a, b, c].into_iter().collect();
+        assert_eq!(keys.len(), 2);
+    }
+
+    #[test]
+    fn reset() {
+        let mut cpc = CpcSketch::new();
+        for key in 0u64..10000 {
+            cpc.update_u64(key);
+        }
+        cpc.reset();
+        assert_eq!(cpc.estimate(), 0.0);
+        assert_eq!(
+            cpc.serialize().as_ref(),
+            CpcSketch::new().serialize().as_ref()
+        );
+        let mut fresh = CpcSketch::new();
+        for key in 20000u64..25000 {
+            cpc.update_u64(key);
+            fresh.update_u64(key);
+        }
+        assert_eq!(cpc.estimate(), fresh.estimate());
+    }
+
+    #[test]
+    fn summary() {
+        let mut cpc = CpcSketch::new();
+        assert!(cpc.summary().is_empty);
+        for key in 0u64..1000 {
+            cpc.update_u64(key);
+        }
+        let summary = cpc.summary();
+        assert_eq!(summary.lg_k, cpc.get_lg_k());
+        assert_eq!(summary.lg_k, 11);
+        assert_eq!(summary.estimate, cpc.estimate());
+        assert!(!summary.is_empty);
+        assert_eq!(summary.serialized_bytes, cpc.serialize().as_ref().len());
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["lg_k"], 11);
+    }
+
+    #[test]
+    fn defaults() {
+        let cpc: CpcSketch = Default::default();
+        assert_eq!(cpc.estimate(), 0.0);
+        let union: CpcUnion = Default::default();
+        assert_eq!(union.sketch().estimate(), 0.0);
+    }
+
     #[test]
     fn union_empty() {
         let cpc = CpcUnion::new().sketch();
@@ -151,6 +829,129 @@ mod tests {
         union.merge(CpcSketch::new());
         let cpc = union.sketch();
         assert_eq!(cpc.estimate(), 0.0);
+        assert_eq!(union.estimate(), 0.0);
+    }
+
+    #[test]
+    fn estimate_rounded() {
+        for n in [0u64, 10, 1000, 100_000].iter().cloned() {
+            let cpc = CpcSketch::from_u64_slice(&(0..n).collect::<Vec<_>>());
+            assert_eq!(cpc.estimate_rounded(), cpc.estimate().round() as u64);
+        }
+        assert_eq!(round_estimate(2.5), 2);
+        assert_eq!(round_estimate(3.5), 4);
+        assert_eq!(round_estimate(1e30), u64::MAX);
+        assert_eq!(round_estimate(f64::INFINITY), u64::MAX);
+    }
+
+    #[test]
+    fn union_estimate() {
+        let sketches: Vec<CpcSketch> = (0..3u64)
+            .map(|i| CpcSketch::from_u64_slice(&(i * 500..i * 500 + 1000).collect::<Vec<_>>()))
+            .collect();
+        let refs: Vec<&CpcSketch> = sketches.iter().collect();
+        let estimate = CpcSketch::union_estimate(&refs);
+
+        let mut union = CpcUnion::new();
+        for sketch in &sketches {
+            union.merge(sketch.clone());
+        }
+        assert_eq!(estimate, union.estimate());
+        assert!((estimate - 2000.0).abs() < 2000.0 * 0.05, "{}", estimate);
+        // the inputs are untouched
+        let first = CpcSketch::from_u64_slice(&(0..1000).collect::<Vec<_>>());
+        assert_eq!(sketches[0].serialize().as_ref(), first.serialize().as_ref());
+        assert_eq!(CpcSketch::union_estimate(&[]), 0.0);
+
+        // from the sparse accumulator as well as the bit matrix
+        for n in [10u64, 100, 1000, 100_000].iter().cloned() {
+            let mut union = CpcUnion::new();
+            union.merge(CpcSketch::from_u64_slice(&(0..n).collect::<Vec<_>>()));
+            union.merge(CpcSketch::from_u64_slice(&(n / 2..n * 2).collect::<Vec<_>>()));
+            assert_eq!(union.estimate(), union.sketch().estimate(), "{}", n);
+        }
+    }
+
+    #[test]
+    fn union_merge_serialized() {
+        let n = 1000;
+        let bufs: Vec<Vec<u8>> = (0..50)
+            .map(|i| {
+                let mut cpc = CpcSketch::new();
+                for key in 0u64..n {
+                    cpc.update_u64(key + i * n / 2);
+                }
+                cpc.serialize_to_vec()
+            })
+            .collect();
+
+        let mut direct = CpcUnion::new();
+        let mut indirect = CpcUnion::new();
+        for buf in &bufs {
+            direct.merge_serialized(buf).unwrap();
+            indirect.merge(CpcSketch::deserialize(buf));
+        }
+        let (direct, indirect) = (direct.sketch(), indirect.sketch());
+        assert_eq!(direct.serialize().as_ref(), indirect.serialize().as_ref());
+        assert_eq!(direct.estimate(), indirect.estimate());
+
+        let mut union = CpcUnion::new();
+        assert!(union.merge_serialized(&bufs[0][..4]).is_err());
+        assert!(union.merge_serialized(b"not a sketch").is_err());
+        assert_eq!(union.sketch().estimate(), 0.0);
+    }
+
+    #[test]
+    fn try_deserialize_malformed() {
+        let mut cpc = CpcSketch::new();
+        cpc.update_u64(1);
+        let bytes = cpc.serialize_to_vec();
+        let bad: &[&[u8]] = &[&bytes[..4], b"not a sketch"];
+        for buf in bad {
+            match CpcSketch::try_deserialize(buf) {
+                Err(DataSketchesError::CppException(_)) => (),
+                other => panic!("{:?} for {:?}", other.map(|c| c.estimate()), buf),
+            }
+        }
+        assert_eq!(CpcSketch::try_deserialize(&bytes).unwrap().estimate(), 1.0);
+    }
+
+    #[test]
+    fn estimate_from_bytes() {
+        let mut union = CpcUnion::new();
+        for &n in &[0u64, 1, 10, 100, 1000, 10 * 1000, 100 * 1000] {
+            let cpc = CpcSketch::from_u64_slice(&(0..n).collect::<Vec<_>>());
+            let bytes = cpc.serialize_to_vec();
+            assert_eq!(CpcSketch::estimate_from_bytes(&bytes).unwrap(), cpc.estimate(), "{}", n);
+            union.merge(cpc);
+            // merged sketches have no HIP estimate, exercising the ICON path
+            let bytes = union.sketch().serialize_to_vec();
+            let expected = CpcSketch::deserialize(&bytes).estimate();
+            assert_eq!(CpcSketch::estimate_from_bytes(&bytes).unwrap(), expected, "{}", n);
+        }
+
+        let bytes = CpcSketch::from_u64_slice(&[1, 2, 3]).serialize_to_vec();
+        let bad: &[&[u8]] = &[&bytes[..4], &bytes[..10], b"not a sketch"];
+        for buf in bad {
+            match CpcSketch::estimate_from_bytes(buf) {
+                Err(DataSketchesError::InvalidBuffer(_)) => (),
+                other => panic!("{:?} for {:?}", other, buf),
+            }
+        }
+    }
+
+    #[test]
+    fn try_deserialize_newer_version() {
+        let mut cpc = CpcSketch::new();
+        cpc.update_u64(1);
+        let mut bytes = cpc.serialize_to_vec();
+        bytes[1] = 255;
+        match CpcSketch::try_deserialize(&bytes) {
+            Err(e @ DataSketchesError::UnsupportedVersion { version: 255, .. }) => {
+                assert_eq!(e.to_string(), "unsupported cpc serial version 255")
+            }
+            other => panic!("{:?}", other.map(|c| c.estimate())),
+        }
     }
 
     #[test]
@@ -191,6 +992,7 @@ mod tests {
             union.merge(cpc);
             let merged = union.sketch();
             let est = merged.estimate();
+            assert_eq!(union.estimate(), est);
             check_cycle(&merged);
             let lb = (n * nrepeats.min(i + 1)) as f64 * 0.95;
             let ub = (n * nrepeats.min(i + 1)) as f64 * 1.05;
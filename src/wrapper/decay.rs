@@ -0,0 +1,139 @@
+//! Distinct counts over a sliding window of time buckets.
+
+use crate::CpcSketch;
+
+/// Estimates distinct values seen over the most recent time buckets, e.g.,
+/// hours or days, so that older observations drop out of the count. This
+/// keeps a fixed ring of [`CpcSketch`]es, one per bucket; a bucket's sketch
+/// is reset once the ring wraps around to it, and
+/// [`estimate_recent`](Self::estimate_recent) unions the sketches of the
+/// buckets in the requested window.
+///
+/// Buckets are arbitrary `u64` ids which should increase with time, such as
+/// the number of hours since the epoch. The window always ends at the latest
+/// bucket observed.
+pub struct DecayingCounter {
+    slots: Vec<Slot>,
+    latest: Option<u64>,
+}
+
+struct Slot {
+    bucket: Option<u64>,
+    sketch: CpcSketch,
+}
+
+impl DecayingCounter {
+    /// Create a counter remembering the last `num_buckets` buckets, which
+    /// is also the widest window it can estimate over.
+    pub fn new(num_buckets: usize) -> Self {
+        assert!(num_buckets > 0, "num_buckets must be positive");
+        Self {
+            slots: (0..num_buckets)
+                .map(|_| Slot {
+                    bucket: None,
+                    sketch: CpcSketch::new(),
+                })
+                .collect(),
+            latest: None,
+        }
+    }
+
+    /// Return the number of buckets the ring holds.
+    pub fn num_buckets(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Return the latest bucket observed, if any.
+    pub fn latest_bucket(&self) -> Option<u64> {
+        self.latest
+    }
+
+    /// Observe `value` in `bucket`, as by [`CpcSketch::update`]. A bucket
+    /// newer than the latest one evicts the buckets which fall out of the
+    /// ring, while values for buckets which already fell out are ignored.
+    pub fn observe(&mut self, value: &[u8], bucket: u64) {
+        let len = self.slots.len() as u64;
+        if let Some(latest) = self.latest {
+            if bucket.saturating_add(len) <= latest {
+                return;
+            }
+        }
+        let slot = &mut self.slots[(bucket % len) as usize];
+        if slot.bucket != Some(bucket) {
+            slot.sketch.reset();
+            slot.bucket = Some(bucket);
+        }
+        slot.sketch.update(value);
+        self.latest = Some(self.latest.map_or(bucket, |latest| latest.max(bucket)));
+    }
+
+    /// Return the estimate of distinct values seen in the `window` buckets
+    /// ending at the latest one, inclusive. `window` may be at most
+    /// [`num_buckets`](Self::num_buckets).
+    pub fn estimate_recent(&self, window: u64) -> f64 {
+        assert!(
+            window <= self.slots.len() as u64,
+            "window {} exceeds {} buckets",
+            window,
+            self.slots.len()
+        );
+        let latest = match self.latest {
+            Some(latest) => latest,
+            None => return 0.0,
+        };
+        let recent: Vec<&CpcSketch> = self
+            .slots
+            .iter()
+            .filter(|slot| matches!(slot.bucket, Some(b) if b.saturating_add(window) > latest))
+            .map(|slot| &slot.sketch)
+            .collect();
+        CpcSketch::union_estimate(&recent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observe_range(counter: &mut DecayingCounter, range: std::ops::Range<u64>, bucket: u64) {
+        for value in range {
+            counter.observe(&value.to_ne_bytes(), bucket);
+        }
+    }
+
+    fn assert_near(estimate: f64, expected: f64) {
+        assert!(
+            (estimate - expected).abs() <= expected * 0.05,
+            "{} vs {}",
+            estimate,
+            expected
+        );
+    }
+
+    #[test]
+    fn old_buckets_drop_out() {
+        let mut counter = DecayingCounter::new(3);
+        assert_eq!(counter.estimate_recent(3), 0.0);
+        for bucket in 0..3 {
+            observe_range(&mut counter, bucket * 1000..(bucket + 1) * 1000, bucket);
+        }
+        assert_eq!(counter.latest_bucket(), Some(2));
+        assert_near(counter.estimate_recent(1), 1000.0);
+        assert_near(counter.estimate_recent(3), 3000.0);
+
+        // advancing two buckets evicts buckets 0 and 1, and skips bucket 3
+        observe_range(&mut counter, 0..500, 4);
+        assert_near(counter.estimate_recent(1), 500.0);
+        assert_near(counter.estimate_recent(2), 500.0);
+        assert_near(counter.estimate_recent(3), 1500.0);
+
+        // late values for an evicted bucket are ignored, but those for a
+        // bucket still in the ring count
+        observe_range(&mut counter, 5000..6000, 1);
+        assert_near(counter.estimate_recent(3), 1500.0);
+        observe_range(&mut counter, 5000..6000, 3);
+        assert_near(counter.estimate_recent(2), 1500.0);
+        assert_eq!(counter.latest_bucket(), Some(4));
+        assert_eq!(counter.estimate_recent(0), 0.0);
+    }
+}
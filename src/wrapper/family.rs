@@ -0,0 +1,109 @@
+//! Identification of serialized sketches by their preamble.
+
+/// The sketch families which can be told apart by [`detect_sketch_family`].
+/// The discriminants are the family IDs shared by all DataSketches
+/// implementations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SketchFamily {
+    /// A Theta sketch in its compact form, i.e., a [`crate::StaticThetaSketch`].
+    ThetaCompact = 3,
+    Hll = 7,
+    Quantiles = 8,
+    Tuple = 9,
+    /// A frequent items sketch in the DataSketches format. This isn't what
+    /// [`crate::HhSketch::serialize`] writes, which is specific to this crate
+    /// and isn't detected.
+    Frequency = 10,
+    Kll = 15,
+    /// A [`crate::CpcSketch`].
+    Cpc = 16,
+    Req = 17,
+}
+
+impl SketchFamily {
+    fn from_id(id: u8) -> Option<Self> {
+        let family = match id {
+            3 => Self::ThetaCompact,
+            7 => Self::Hll,
+            8 => Self::Quantiles,
+            9 => Self::Tuple,
+            10 => Self::Frequency,
+            15 => Self::Kll,
+            16 => Self::Cpc,
+            17 => Self::Req,
+            _ => return None,
+        };
+        Some(family)
+    }
+}
+
+/// Every serialized sketch starts with at least this many bytes of preamble.
+const MIN_PREAMBLE_BYTES: usize = 8;
+
+/// Inspect the preamble of a serialized sketch to determine which family it
+/// belongs to, so that it can be passed to the right deserializer. Returns
+/// `None` if `buf` is too short to be a sketch or names an unknown family.
+///
+/// Only the header is checked; a `Some` result does not guarantee that
+/// deserialization will succeed.
+pub fn detect_sketch_family(buf: &[u8]) -> Option<SketchFamily> {
+    if buf.len() < MIN_PREAMBLE_BYTES {
+        return None;
+    }
+    // The preamble size and serial version are never zero.
+    let (preamble_size, serial_version, family_id) = (buf[0], buf[1], buf[2]);
+    if preamble_size == 0 || serial_version == 0 {
+        return None;
+    }
+    SketchFamily::from_id(family_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CpcSketch, ThetaSketch};
+
+    #[test]
+    fn detects_wrapped_sketches() {
+        let mut cpc = CpcSketch::new();
+        let mut theta = ThetaSketch::new();
+        assert_eq!(
+            detect_sketch_family(&cpc.serialize_to_vec()),
+            Some(SketchFamily::Cpc)
+        );
+        assert_eq!(
+            detect_sketch_family(&theta.as_static().serialize_to_vec()),
+            Some(SketchFamily::ThetaCompact)
+        );
+        for key in 0u64..10000 {
+            cpc.update_u64(key);
+            theta.update_u64(key);
+        }
+        assert_eq!(
+            detect_sketch_family(&cpc.serialize_to_vec()),
+            Some(SketchFamily::Cpc)
+        );
+        assert_eq!(
+            detect_sketch_family(&theta.as_static().serialize_to_vec()),
+            Some(SketchFamily::ThetaCompact)
+        );
+    }
+
+    #[test]
+    fn detects_foreign_headers() {
+        // Preambles of empty sketches as serialized by datasketches-cpp.
+        let hll = [2, 1, 7, 12, 4, 16, 0, 0];
+        let kll = [2, 2, 15, 1, 200, 0, 8, 0];
+        assert_eq!(detect_sketch_family(&hll), Some(SketchFamily::Hll));
+        assert_eq!(detect_sketch_family(&kll), Some(SketchFamily::Kll));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(detect_sketch_family(b""), None);
+        assert_eq!(detect_sketch_family(&[1, 1, 16]), None);
+        assert_eq!(detect_sketch_family(&[0; 16]), None);
+        assert_eq!(detect_sketch_family(&[1, 1, 200, 0, 0, 0, 0, 0]), None);
+        assert_eq!(detect_sketch_family(b"hello, world!"), None);
+    }
+}
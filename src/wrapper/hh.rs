@@ -3,17 +3,23 @@
 use std::ptr::NonNull;
 use std::slice;
 use std::borrow::Borrow;
-use std::collections::HashSet;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+use std::convert::TryInto;
+use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::str;
 
-use cxx;
+use serde::Serialize;
 use thin_dst::{ThinRef,ThinBox};
 
 use crate::bridge::ffi;
+use crate::DataSketchesError;
 
 /// A type around a thin box to a byte buffer. Still basically just a pointer,
 /// but lets us implement `Borrow<[u8]>` semantics for use as hash structure keys.
-struct ThinByteBox(ThinBox<(), u8>);
+/// The head holds the seeded hash of the buffer, see [`key_hash`].
+struct ThinByteBox(ThinBox<u64, u8>);
 
 impl Borrow<[u8]> for ThinByteBox {
     fn borrow(&self) -> &[u8] {
@@ -38,6 +44,24 @@ impl PartialEq for ThinByteBox {
 impl Eq for ThinByteBox {
 }
 
+impl ThinByteBox {
+    /// The address the C++ sketch knows this key by.
+    fn addr(&self) -> usize {
+        let thinref = ThinRef::<u64, u8>::from(&*self.0);
+        ThinRef::<u64, u8>::erase(thinref).as_ptr() as *const _ as usize
+    }
+}
+
+/// The interned keys of a [`HhSketch`], along with a count of how many times
+/// the C++ side asked to remove a key which was not present. The latter should
+/// never happen, but if the two sides fall out of sync we'd rather report it
+/// than abort.
+#[derive(Default)]
+struct Intern {
+    keys: HashSet<ThinByteBox>,
+    missing_removals: u64,
+}
+
 /// The [Heavy Hitter][orig-docs] (HH) sketch computes an approximate set of the
 /// heavy hitters, the items in a data stream which appear most often. Along with
 /// each proposed approximate heavy hitter, the sketch can provide an estimate of
@@ -64,6 +88,7 @@ impl Eq for ThinByteBox {
 ///   - simplifies memory management across language boundaries
 ///   - makes hashing with Rust algorithms easier
 ///   - avoids allocation on updates for strings already in the sketch
+///
 /// at the cost of constant additional per-string overhead (at 90% Rust HashMap load
 /// factor, about ~11 bytes) and needless hashing overhead on the C++ side. While the C++ side
 /// does not own the string values, when it removes a key from the hash, the key is deleted from
@@ -79,8 +104,38 @@ pub struct HhSketch {
     inner: cxx::UniquePtr<ffi::OpaqueHhSketch>,
     /// Bytestring keys are stored here; the C++ implementation refers to the byte slice
     /// _addresses_ as the unique keys in the heavy hitter sketch.
-    intern: Box<HashSet<ThinByteBox>>, // boxed for stable address
-    lg2_k: u8
+    intern: Box<Intern>, // boxed for stable address
+    lg2_k: u8,
+    seed: u64,
+}
+
+/// Seed used by [`HhSketch::new`], matching the datasketches default.
+const DEFAULT_SEED: u64 = 9001;
+
+/// Default extra log2 sizing for [`HhSketch::for_top_k`].
+const DEFAULT_HEADROOM: u8 = 2;
+
+/// Smallest log2 sketch size the underlying sketch supports.
+const MIN_LG2_K: u8 = 3;
+
+/// Largest log2 sketch size [`HhSketch::for_top_k`] will allocate.
+pub const MAX_LG2_K: u8 = 21;
+
+// https://users.rust-lang.org/t/logarithm-of-integers/8506/5
+
+fn log2_floor(x: u64) -> usize {
+    const fn num_bits<T>() -> usize { std::mem::size_of::<T>() * 8 }
+    assert!(x > 0);
+    num_bits::<u64>() - x.leading_zeros() as usize - 1
+}
+
+/// The hash the C++ sketch uses for a key, which depends only on the key's
+/// contents and the sketch's seed, rather than on the (run-dependent)
+/// address of its interned copy. It's MurmurHash3, whose output is fixed,
+/// unlike that of std's `DefaultHasher`, so sketches built by different
+/// builds of this crate lay out their tables the same way.
+fn key_hash(seed: u64, value: &[u8]) -> u64 {
+    ffi::hh_key_hash(value, seed)
 }
 
 /// An entry in the heavy hitters sketch.
@@ -91,16 +146,93 @@ pub struct HhRow<'a> {
     pub ub: u64,
 }
 
+impl HhRow<'_> {
+    /// Return the key for display, as is if it's valid UTF-8, and
+    /// otherwise as its bytes in hex prefixed by `0x`, e.g., `0xff00`;
+    /// see [`DisplayKey`].
+    pub fn display_key(&self) -> impl fmt::Display + '_ {
+        DisplayKey(self.key)
+    }
+}
+
+/// Formats the row as `key (lb..ub)`, with the key as in [`HhRow::display_key`].
+impl fmt::Display for HhRow<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}..{})", self.display_key(), self.lb, self.ub)
+    }
+}
+
+/// Formats a key as is if it's valid UTF-8, and otherwise as its bytes in
+/// hex prefixed by `0x`, e.g., `0xff00`, so that distinct keys which aren't
+/// valid UTF-8 don't collapse to the same replacement characters. This is
+/// for display only: a valid UTF-8 key that itself starts with `0x` may
+/// print the same as a non-UTF-8 one.
+pub struct DisplayKey<'a>(pub &'a [u8]);
+
+impl fmt::Display for DisplayKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Ok(key) = str::from_utf8(self.0) {
+            return f.write_str(key);
+        }
+        f.write_str("0x")?;
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Which kind of error a heavy hitter query should avoid, see
+/// [`HhSketch::estimate`].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum HhErrorType {
+    /// Only return items which are guaranteed to be heavy hitters, i.e.,
+    /// whose frequency lower bound exceeds the sketch's maximum error.
+    NoFalsePositives,
+    /// Return every item which may be a heavy hitter, i.e., whose frequency
+    /// upper bound exceeds the sketch's maximum error.
+    NoFalseNegatives,
+}
+
+/// Orders rows by upper bound, with ties broken by lower bound and then in
+/// favor of smaller keys, so that greater rows are heavier. This is a total
+/// order, since keys are unique.
+struct ByUpperBound<'a>(HhRow<'a>);
+
+impl Ord for ByUpperBound<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .ub
+            .cmp(&other.0.ub)
+            .then_with(|| self.0.lb.cmp(&other.0.lb))
+            .then_with(|| other.0.key.cmp(self.0.key))
+    }
+}
+
+impl PartialOrd for ByUpperBound<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ByUpperBound<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ByUpperBound<'_> {}
+
 /// Function safety must be justified due to lifetime construction
-unsafe fn addr_to_thinref<'a>(addr: usize) -> ThinRef<'a, (), u8> {
+unsafe fn addr_to_thinref<'a>(addr: usize) -> ThinRef<'a, u64, u8> {
     // not actually used as mut, which would be unsafe
     let ptr = addr as *mut _;    
     let nonnull = NonNull::<_>::new(ptr).expect("non-null pointer");
-    ThinRef::<'a, (), u8>::from_erased(nonnull)
+    ThinRef::<'a, u64, u8>::from_erased(nonnull)
 }
 
 /// Function safety must be justified due to lifetime construction
-unsafe fn addr_to_hashset<'a>(addr: usize) -> &'a mut HashSet<ThinByteBox> {
+unsafe fn addr_to_intern<'a>(addr: usize) -> &'a mut Intern {
     // safe as mut because we only call this in callbacks in &mut self methods
     let ptr = addr as *mut _;
     let mut nonnull = NonNull::<_>::new(ptr).expect("non-null pointer");
@@ -112,17 +244,29 @@ unsafe fn addr_to_hashset<'a>(addr: usize) -> &'a mut HashSet<ThinByteBox> {
 ///
 ///   1. Has been borrowed as `Pin<&mut>` for the duration of the C++ call invoking this
 ///      FFI-intended function.
-///   2. The corresponding addresses refer to the hashset and one of its keys from
+///   2. The corresponding addresses refer to the intern and one of its keys from
 ///      the `HhSketch` in question.
+///
+/// A key missing from the intern is recorded rather than treated as fatal,
+/// see [`HhSketch::check_consistency`].
 pub(crate) unsafe fn remove_from_hashset(hashset_addr:usize, addr: usize) {
     // eprintln!("remove_from_hashset({},{})", hashset_addr, addr);
-    let hs = addr_to_hashset(hashset_addr);
+    let intern = addr_to_intern(hashset_addr);
     let thinref = addr_to_thinref(addr);
     // use byte_slice_cast::AsSliceOf;
     // eprintln!("  val {}", thinref.slice.as_slice_of::<u64>().unwrap()[0]);
-    let did_remove = hs.remove(&thinref.slice);
+    let did_remove = intern.keys.remove(&thinref.slice);
     // eprintln!("  hashset contains? {}", did_remove);
-    assert!(did_remove, "thinbox {:?}", thinref);
+    if !did_remove {
+        intern.missing_removals += 1;
+    }
+}
+
+/// Function is only safe to call so long as `addr` refers to a key interned by an
+/// [`HhSketch`] which is borrowed for the duration of the C++ call invoking this
+/// FFI-intended function.
+pub(crate) unsafe fn interned_key_hash(addr: usize) -> u64 {
+    addr_to_thinref(addr).head
 }
 
 impl HhSketch {
@@ -130,13 +274,68 @@ impl HhSketch {
     /// and together with the (runtime-determined) stream size `n` the heavy hitters
     /// which occur at least `n/k` times are to be found with high probability. Richer
     /// guarantees exist; see related work cited in the struct documentation.
+    ///
+    /// The sketch is deterministic: the same sequence of updates always results in
+    /// the same state. See [`Self::with_seed`] to vary it.
     pub fn new(lg2_k: u8) -> Self {
-        let intern = Box::new(HashSet::<_>::default());
-        Self {
-            inner: ffi::new_opaque_hh_sketch(lg2_k, intern.as_ref() as *const _ as usize),
+        Self::with_seed(lg2_k, DEFAULT_SEED)
+    }
+
+    /// Create a HH sketch as in [`Self::new`], but with a specific `seed` for the
+    /// hash of keys. The hash determines the layout of the sketch's internal table,
+    /// which in turn determines which items are sampled to find the median weight
+    /// when the sketch purges small items, and the order of ties in query results.
+    /// Sketches with the same seed and the same updates are identical, even
+    /// across different builds of this crate.
+    pub fn with_seed(lg2_k: u8, seed: u64) -> Self {
+        Self::try_with_seed(lg2_k, seed).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Create a HH sketch targeting elements in the top-k by reserving O(k)
+    /// space, with a default headroom of 2; see [`Self::for_top_k_with_headroom`].
+    pub fn for_top_k(k: u64) -> Self {
+        Self::for_top_k_with_headroom(k, DEFAULT_HEADROOM)
+    }
+
+    /// Create a HH sketch targeting elements in the top-k, sized at
+    /// `2^(floor(log2(k)) + extra_lg2)` (but at least 2 before the headroom is added).
+    ///
+    /// The sketch only tracks up to 3/4 of its size in items at once, and the
+    /// guarantee to find items with frequency above `n/k` needs an extra factor
+    /// of two over that, so a headroom of at least 2 is needed for the top-k to
+    /// be found with high probability. Larger headrooms tighten the count
+    /// bounds at the cost of space. The size is clamped between 2^3, the
+    /// smallest supported, and `2^MAX_LG2_K`, i.e., 2^21.
+    pub fn for_top_k_with_headroom(k: u64, extra_lg2: u8) -> Self {
+        let lg2_k = log2_floor(k).max(1) + usize::from(extra_lg2);
+        let lg2_k = lg2_k.clamp(MIN_LG2_K.into(), MAX_LG2_K.into()) as u8;
+        Self::new(lg2_k)
+    }
+
+    /// Create a HH sketch as in [`Self::new`], returning an error rather than
+    /// panicking if datasketches-cpp rejects `lg2_k`, e.g., when it is below 3.
+    pub fn try_new(lg2_k: u8) -> Result<Self, DataSketchesError> {
+        Self::try_with_seed(lg2_k, DEFAULT_SEED)
+    }
+
+    /// Create a HH sketch as in [`Self::with_seed`], returning an error rather
+    /// than panicking if datasketches-cpp rejects `lg2_k`.
+    pub fn try_with_seed(lg2_k: u8, seed: u64) -> Result<Self, DataSketchesError> {
+        Self::try_sized(lg2_k, MIN_LG2_K, seed)
+    }
+
+    /// Create a sketch whose internal table starts out at `2^lg_start_size`
+    /// rather than the smallest size, e.g., to restore a serialized sketch's
+    /// table as it was.
+    fn try_sized(lg2_k: u8, lg_start_size: u8, seed: u64) -> Result<Self, DataSketchesError> {
+        let intern = Box::new(Intern::default());
+        let hashset_addr = intern.as_ref() as *const _ as usize;
+        Ok(Self {
+            inner: ffi::new_opaque_hh_sketch(lg2_k, lg_start_size, hashset_addr)?,
             intern,
             lg2_k,
-        }
+            seed,
+        })
     }
 
     fn thin_row_to_owned<'a>(&'a self, row: &ffi::ThinHeavyHitterRow) -> HhRow<'a> {
@@ -149,63 +348,404 @@ impl HhSketch {
         }
     }
 
+    /// Return the heavy hitters avoiding the given type of error, their
+    /// frequency lower bound, and their frequency upper bound.
+    pub fn estimate(&self, error_type: HhErrorType) -> Vec<HhRow<'_>> {
+        self.rows(error_type).collect()
+    }
+
     /// Return the heavy hitters with no false positives, their
     /// frequency lower bound, and their frequency upper bound.
-    pub fn estimate_no_fp(&self) -> Vec<HhRow> {
-        self.inner
-            .estimate_no_fp()
-            .into_iter()
-            .map(|x| self.thin_row_to_owned(x))
-            .collect()
+    pub fn estimate_no_fp(&self) -> Vec<HhRow<'_>> {
+        self.estimate(HhErrorType::NoFalsePositives)
     }
 
     /// Return the heavy hitters with no false negatives; this is less
     /// conservative than [`Self::estimate_no_fp`].
-    pub fn estimate_no_fn(&self) -> Vec<HhRow> {
-        self.inner
-            .estimate_no_fn()
+    pub fn estimate_no_fn(&self) -> Vec<HhRow<'_>> {
+        self.estimate(HhErrorType::NoFalseNegatives)
+    }
+
+    /// Like [`Self::estimate_no_fp`], but lazily yields rows from the
+    /// C++ result rather than collecting them into a [`Vec`].
+    pub fn iter_no_fp(&self) -> impl Iterator<Item = HhRow<'_>> {
+        self.rows(HhErrorType::NoFalsePositives)
+    }
+
+    /// Like [`Self::estimate_no_fn`], but lazily yields rows from the
+    /// C++ result rather than collecting them into a [`Vec`].
+    pub fn iter_no_fn(&self) -> impl Iterator<Item = HhRow<'_>> {
+        self.rows(HhErrorType::NoFalseNegatives)
+    }
+
+    fn rows(&self, error_type: HhErrorType) -> impl ExactSizeIterator<Item = HhRow<'_>> {
+        let rows = match error_type {
+            HhErrorType::NoFalsePositives => self.inner.estimate_no_fp(),
+            HhErrorType::NoFalseNegatives => self.inner.estimate_no_fn(),
+        };
+        (0..rows.len()).map(move |i| self.thin_row_to_owned(&rows.as_slice()[i]))
+    }
+
+    /// Return the heavy hitters whose frequency exceeds `fraction` of the
+    /// total weight, in the same order as [`Self::top_k`]. With
+    /// [`HhErrorType::NoFalsePositives`], an item's lower bound must exceed
+    /// the threshold, and with [`HhErrorType::NoFalseNegatives`], its upper
+    /// bound. Unlike [`Self::estimate`], the threshold isn't raised to the
+    /// sketch's maximum error, so small fractions may return items the
+    /// sketch can't tell apart from noise. `fraction` must be in `(0, 1]`,
+    /// as for [`crate::counters::HeavyHitter::with_threshold`].
+    pub fn estimate_above_threshold(
+        &self,
+        fraction: f64,
+        error_type: HhErrorType,
+    ) -> Vec<HhRow<'_>> {
+        assert!(
+            fraction > 0.0 && fraction <= 1.0,
+            "fraction {} not in (0, 1]",
+            fraction
+        );
+        let threshold = (fraction * self.get_total_weight() as f64) as u64;
+        let state = self.inner.state();
+        let mut rows: Vec<_> = state
+            .iter()
+            .map(|row| self.thin_row_to_owned(row))
+            .filter(|row| match error_type {
+                HhErrorType::NoFalsePositives => row.lb > threshold,
+                HhErrorType::NoFalseNegatives => row.ub > threshold,
+            })
+            .collect();
+        rows.sort_unstable_by(|a, b| ByUpperBound(b.clone()).cmp(&ByUpperBound(a.clone())));
+        rows
+    }
+
+    /// Return the (at most) `k` heavy hitters with the largest upper bounds,
+    /// sorted by upper bound in descending order, with ties broken by lower
+    /// bound (also descending) and then by key, so the order only depends on
+    /// the rows and not the order they were observed in.
+    ///
+    /// Selection uses a bounded heap, so this takes `O(n log k)` time for
+    /// `n` rows returned by the `error_type` query.
+    pub fn top_k(&self, k: usize, error_type: HhErrorType) -> Vec<HhRow<'_>> {
+        if k == 0 {
+            return Vec::new();
+        }
+        let rows = self.rows(error_type);
+        let mut heap = BinaryHeap::with_capacity(k.min(rows.len()) + 1);
+        for row in rows {
+            heap.push(Reverse(ByUpperBound(row)));
+            if heap.len() > k {
+                heap.pop();
+            }
+        }
+        heap.into_sorted_vec()
             .into_iter()
-            .map(|x| self.thin_row_to_owned(x))
+            .map(|Reverse(ByUpperBound(row))| row)
             .collect()
     }
     
-    /// Observe a new value.
+    /// Observe a new value with the given weight.
+    ///
+    /// A zero `weight` is a no-op. Decrements (retractions of previously
+    /// observed weight) are not supported: the underlying Misra-Gries
+    /// variant only ever accumulates weight, and its bounds would no longer
+    /// hold if an item's count could shrink, so any retractions have to be
+    /// resolved before values are fed to the sketch.
+    ///
+    /// Panics if the sketch's total weight would overflow a `u64`, which would
+    /// otherwise silently corrupt its counts; see [`Self::try_update`].
     pub fn update(&mut self, value: &[u8], weight: u64) {
+        if weight == 0 {
+            // The C++ side ignores zero weights, so the key must not be
+            // interned here either, as nothing would ever remove it.
+            return;
+        }
+        if let Err(e) = self.check_weight(weight) {
+            panic!("{}", e);
+        }
         // TODO: once this hash_set_entry API merges, this approach can save
         // on two (!) needless hash re-computations.
         // #![feature(hash_set_entry)]
-        // let key = self.intern.get_or_insert_with::<[u8], _>(value, |buf| {
-        // ThinByteBox(ThinBox::new((), buf.iter().cloned()))
+        // let key = self.intern.keys.get_or_insert_with::<[u8], _>(value, |buf| {
+        // ThinByteBox(ThinBox::new(key_hash(self.seed, buf), buf.iter().cloned()))
         // });
-        let key = if let Some(key) = self.intern.get(value) {
-            &*key.0
+        let key = if let Some(key) = self.intern.keys.get(value) {
+            key.addr()
         } else {
-            let key = ThinByteBox(ThinBox::new((), value.iter().cloned()));
-            self.intern.insert(key);
-            &*self.intern.get(value).expect("present key").0
+            let hash = key_hash(self.seed, value);
+            let key = ThinByteBox(ThinBox::new(hash, value.iter().cloned()));
+            let addr = key.addr();
+            self.intern.keys.insert(key);
+            addr
         };
-        let thinref = ThinRef::<(), u8>::from(key);
-        let key = ThinRef::<(), u8>::erase(thinref).as_ptr() as *const _ as usize;
         self.inner.pin_mut().update(key, weight)
     }
 
+    /// Observe a new value, given by its bytes, with the given weight. This is
+    /// equivalent to `update()` on the collected bytes, but copies them straight
+    /// into the sketch's own storage, at the cost of an allocation even when the
+    /// value was already present.
+    pub fn update_from<I>(&mut self, bytes: I, weight: u64)
+    where
+        I: IntoIterator<Item = u8>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        if weight == 0 {
+            return;
+        }
+        if let Err(e) = self.check_weight(weight) {
+            panic!("{}", e);
+        }
+        let mut key = ThinByteBox(ThinBox::new(0, bytes));
+        key.0.head = key_hash(self.seed, &key.0.slice);
+        let key = if let Some(existing) = self.intern.keys.get(&key) {
+            existing.addr()
+        } else {
+            let addr = key.addr();
+            self.intern.keys.insert(key);
+            addr
+        };
+        self.inner.pin_mut().update(key, weight)
+    }
+
+    /// Observe a new value with the given weight as in [`Self::update`], but
+    /// return an error rather than panicking if the sketch's total weight would
+    /// overflow, leaving the sketch unchanged. Since every count and error bound
+    /// is at most the total weight, none of them can overflow either.
+    pub fn try_update(&mut self, value: &[u8], weight: u64) -> Result<(), DataSketchesError> {
+        self.check_weight(weight)?;
+        self.update(value, weight);
+        Ok(())
+    }
+
+    fn check_weight(&self, weight: u64) -> Result<(), DataSketchesError> {
+        let total_weight = self.get_total_weight();
+        match total_weight.checked_add(weight) {
+            Some(_) => Ok(()),
+            None => Err(DataSketchesError::WeightOverflow {
+                total_weight,
+                weight,
+            }),
+        }
+    }
+
+    /// Observe a new string with the given weight, equivalent to calling
+    /// `update()` on its UTF-8 bytes.
+    pub fn update_str(&mut self, value: &str, weight: u64) {
+        self.update(value.as_bytes(), weight)
+    }
+
+    /// Return the log2 of the maximum size of the sketch's internal table.
+    pub fn get_lg2_k(&self) -> u8 {
+        self.lg2_k
+    }
+
+    /// Return the sketch's key parameters and current state, e.g., for logging.
+    pub fn summary(&self) -> HhSummary {
+        HhSummary {
+            lg2_k: self.get_lg2_k(),
+            total_weight: self.get_total_weight(),
+            num_active_items: self.intern.keys.len(),
+        }
+    }
+
+    /// Return the maximum number of items the sketch can track at once,
+    /// three quarters of `2^lg2_k`. Once more distinct items arrive, the sketch
+    /// purges those with small counts.
+    pub fn capacity(&self) -> u32 {
+        (3u32 << self.lg2_k) / 4
+    }
+
+    /// Return the number of items the sketch currently tracks.
+    pub fn get_num_active_items(&self) -> u32 {
+        self.inner.get_num_active_items()
+    }
+
+    /// Return the sum of all weights observed by this sketch, including
+    /// those of items which have since been evicted.
+    pub fn get_total_weight(&self) -> u64 {
+        self.inner.get_total_weight()
+    }
+
+    /// Check that the C++ sketch and the Rust-side key storage agree. Every key
+    /// the C++ sketch evicts should be present in the Rust storage; this returns
+    /// an error reporting how many evictions were not, if any. Such a desync would
+    /// be a bug in this crate, but does not cause memory unsafety, so it is
+    /// surfaced here rather than by aborting.
+    pub fn check_consistency(&self) -> Result<(), DataSketchesError> {
+        match self.intern.missing_removals {
+            0 => Ok(()),
+            missing_removals => Err(DataSketchesError::Desync { missing_removals }),
+        }
+    }
+
+    /// Merge `other` into `self`. This mirrors datasketches-cpp's native merge:
+    /// each of `other`'s counters is re-inserted with its stored count (its lower
+    /// bound), and the stored counts' error terms are then added, so upper bounds
+    /// remain valid. Merged estimates may be looser than those of a single sketch
+    /// over the concatenated streams, but are not biased relative to the C++ merge.
+    ///
+    /// Panics if the combined total weight would overflow a `u64`; see
+    /// [`Self::try_merge`].
     pub fn merge(&mut self, other: &Self) {
+        self.try_merge(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Merge `other` into `self` as in [`Self::merge`], but return an error
+    /// rather than panicking if the combined total weight would overflow,
+    /// leaving the sketch unchanged. The total includes weight `other` has
+    /// already evicted, so checking its retained rows alone isn't enough.
+    pub fn try_merge(&mut self, other: &Self) -> Result<(), DataSketchesError> {
+        let total_weight = self.get_total_weight();
+        let total_weight = total_weight.checked_add(other.get_total_weight()).ok_or(
+            DataSketchesError::WeightOverflow {
+                total_weight,
+                weight: other.get_total_weight(),
+            },
+        )?;
         let state = other.inner.state();
-        let total_weight = self.inner.get_total_weight() + other.inner.get_total_weight();
         for row in state.iter() {
             let row = other.thin_row_to_owned(row);
             self.update(row.key, row.lb);
         }
-        let offset = self.inner.get_offset() + other.inner.get_offset();
+        // the offset bounds the error of the merged counts, which can't
+        // exceed the merged total weight, so it can't overflow either
+        let offset = self
+            .inner
+            .get_offset()
+            .checked_add(other.inner.get_offset())
+            .expect("offset within total weight");
         self.inner.pin_mut().set_weights(total_weight, offset);
+        Ok(())
+    }
+
+    /// Serialize the sketch, along with its keys, into a buffer which
+    /// [`Self::deserialize`] restores to an identical sketch. Besides the same
+    /// rows and weights, the copy lays out its internal table the same way, so
+    /// further updates affect both alike, e.g., to checkpoint a long-running
+    /// stream. The format is specific to this crate, since datasketches-cpp's
+    /// own only holds the addresses of the interned keys.
+    ///
+    /// The buffer starts with `lg2_k` and the log2 of the table's current size
+    /// as bytes, then the seed, total weight and offset as little-endian
+    /// `u64`s. Each item follows with its weight and its key's length as
+    /// little-endian `u64`s, then the key itself.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = vec![self.lg2_k, self.inner.get_lg_cur_size()];
+        for x in &[self.seed, self.get_total_weight(), self.inner.get_offset()] {
+            buf.extend_from_slice(&x.to_le_bytes());
+        }
+        for row in self.inner.slot_order_state().iter() {
+            let row = self.thin_row_to_owned(row);
+            buf.extend_from_slice(&row.lb.to_le_bytes());
+            buf.extend_from_slice(&(row.key.len() as u64).to_le_bytes());
+            buf.extend_from_slice(row.key);
+        }
+        buf
+    }
+
+    /// Deserialize a sketch written by [`Self::serialize`], panicking if the
+    /// buffer is malformed; see [`Self::try_deserialize`].
+    pub fn deserialize(buf: &[u8]) -> Self {
+        Self::try_deserialize(buf).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Deserialize a sketch written by [`Self::serialize`], returning an error
+    /// if the buffer is truncated or its items don't fit the table it
+    /// describes.
+    pub fn try_deserialize(mut buf: &[u8]) -> Result<Self, DataSketchesError> {
+        fn invalid(msg: &str) -> DataSketchesError {
+            DataSketchesError::InvalidBuffer(format!("hh sketch {}", msg))
+        }
+        fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], DataSketchesError> {
+            if buf.len() < len {
+                return Err(invalid("truncated"));
+            }
+            let (head, rest) = buf.split_at(len);
+            *buf = rest;
+            Ok(head)
+        }
+        fn take_u64(buf: &mut &[u8]) -> Result<u64, DataSketchesError> {
+            let bytes = take(buf, 8)?;
+            Ok(u64::from_le_bytes(bytes.try_into().expect("8 bytes")))
+        }
+
+        let sizes = take(&mut buf, 2)?;
+        let (lg2_k, lg_cur_size) = (sizes[0], sizes[1]);
+        let seed = take_u64(&mut buf)?;
+        let total_weight = take_u64(&mut buf)?;
+        let offset = take_u64(&mut buf)?;
+        let mut sketch = Self::try_sized(lg2_k, lg_cur_size, seed)?;
+        while !buf.is_empty() {
+            let weight = take_u64(&mut buf)?;
+            let len = take_u64(&mut buf)?.try_into().unwrap_or(usize::MAX);
+            let key = take(&mut buf, len)?;
+            if weight == 0 {
+                return Err(invalid("item has zero weight"));
+            }
+            sketch.try_update(key, weight)?;
+        }
+        // Replaying the items must neither grow nor purge the table, or it
+        // would no longer be laid out as serialized.
+        if sketch.inner.get_lg_cur_size() != lg_cur_size || sketch.inner.get_offset() != 0 {
+            return Err(invalid("has more items than its table holds"));
+        }
+        if sketch.get_total_weight() > total_weight {
+            return Err(invalid("item weights exceed its total weight"));
+        }
+        sketch.inner.pin_mut().set_weights(total_weight, offset);
+        Ok(sketch)
     }
 }
 
+/// A snapshot of a [`HhSketch`], see [`HhSketch::summary`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct HhSummary {
+    pub lg2_k: u8,
+    pub total_weight: u64,
+    /// Number of distinct items the sketch currently tracks.
+    pub num_active_items: usize,
+}
+
+/// Iterating over a sketch yields its heavy hitters with no false negatives,
+/// as in [`HhSketch::estimate_no_fn`].
+impl<'a> IntoIterator for &'a HhSketch {
+    type Item = HhRow<'a>;
+    type IntoIter = std::vec::IntoIter<HhRow<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.estimate_no_fn().into_iter()
+    }
+}
+
+/// Clones copy the C++ sketch as is, so unlike a fresh sketch [`merge`]d
+/// with the original, the copy has the same rows, weights and error.
+///
+/// [`merge`]: HhSketch::merge
 impl Clone for HhSketch {
     fn clone(&self) -> Self {
-        let mut hh = Self::new(self.lg2_k);
-        hh.merge(self);
-        hh
+        let mut intern = Box::new(Intern {
+            keys: HashSet::with_capacity(self.intern.keys.len()),
+            missing_removals: self.intern.missing_removals,
+        });
+        let mut addrs = Vec::with_capacity(self.intern.keys.len());
+        for key in &self.intern.keys {
+            let copy = ThinByteBox(ThinBox::new(key.0.head, key.0.slice.iter().cloned()));
+            addrs.push((key.addr(), copy.addr()));
+            intern.keys.insert(copy);
+        }
+        addrs.sort_unstable();
+        let (old_addrs, new_addrs): (Vec<_>, Vec<_>) = addrs.into_iter().unzip();
+        let hashset_addr = intern.as_ref() as *const _ as usize;
+        let inner = self
+            .inner
+            .clone_relinked(hashset_addr, &old_addrs, &new_addrs)
+            .unwrap_or_else(|e| panic!("{}", e));
+        Self {
+            inner,
+            intern,
+            lg2_k: self.lg2_k,
+            seed: self.seed,
+        }
     }
 }
 
@@ -263,7 +803,7 @@ mod tests {
 
     /// Makes sure that all keys in `expected` are present with the expected frequency.
     fn matches(hh: &HhSketch, expected: &[(u64, u64)]) {
-        let present = row2keys(&hh)
+        let present = row2keys(hh)
             .into_iter().map(|(key, lb, ub)| {
 	        (key, (lb, ub))
             }).collect::<HashMap<_, _>>();
@@ -276,7 +816,7 @@ mod tests {
     }
 
     fn matches_violations(hh: &HhSketch, expected: &[(u64, u64)]) -> usize {
-        let present = row2keys(&hh)
+        let present = row2keys(hh)
             .into_iter().map(|(key, lb, ub)| {
 	        (key, (lb, ub))
             }).collect::<HashMap<_, _>>();
@@ -291,7 +831,7 @@ mod tests {
                 violations += 1;
             }
         }
-        return violations
+        violations
     }
 
 
@@ -388,7 +928,7 @@ mod tests {
                     let slice = [i];
                     hh.update(slice.as_byte_slice(), 1)
                 }
-                check_cycle(&hh);
+                check_cycle(hh);
             }
             let mut hh = hhs.pop().expect("some last");
             hhs.into_iter().for_each(|other| hh.merge(&other));
@@ -411,7 +951,7 @@ mod tests {
         use rand::prelude::*;
         let k: u64 = 1u64 << lg2_k;
         let n = k * (stream_multiplier as u64);
-        let thresh = (7 * (stream_multiplier as u64) + 1) / 2;
+        let thresh = (7 * (stream_multiplier as u64)).div_ceil(2);
 
         let mut histogram = match nunique {
 	    1 => {
@@ -450,9 +990,7 @@ mod tests {
         };
 
         let sum = histogram.iter().cloned().sum::<u64>();
-        for _ in sum..n {
-            histogram.push(1);
-        }
+        histogram.resize(histogram.len() + (n - sum) as usize, 1);
 
         let mut data = histogram.iter().cloned().enumerate()
             .flat_map(|(i, repeats)| iter::repeat(i as u64).take(repeats as usize))
@@ -541,4 +1079,486 @@ mod tests {
         assert!(hh.estimate_no_fn().is_empty());
         check_cycle(&hh);
     }
+
+    #[test]
+    fn zero_weight_noop() {
+        let mut hh = HhSketch::new(4);
+        hh.update(b"a", 3);
+        hh.update(b"b", 0);
+        assert_eq!(hh.get_total_weight(), 3);
+        assert_eq!(hh.intern.keys.len(), 1);
+        let rows = hh.estimate_no_fn();
+        assert_eq!(rows, vec![HhRow { key: b"a", lb: 3, ub: 3 }]);
+
+        let mut empty = HhSketch::new(4);
+        empty.update(b"a", 0);
+        assert_eq!(empty.get_total_weight(), 0);
+        assert!(empty.intern.keys.is_empty());
+        assert!(empty.estimate_no_fn().is_empty());
+        check_cycle(&empty);
+    }
+
+    #[test]
+    fn iter_matches_vec() {
+        let mut hh = HhSketch::new(3);
+        for i in 0u64..100 {
+            let slice = [i % 13];
+            hh.update(slice.as_byte_slice(), i % 5 + 1);
+        }
+        assert_eq!(hh.iter_no_fn().collect::<Vec<_>>(), hh.estimate_no_fn());
+        assert_eq!(hh.iter_no_fp().collect::<Vec<_>>(), hh.estimate_no_fp());
+        assert!(!hh.estimate_no_fn().is_empty());
+    }
+
+    #[test]
+    fn top_k_matches_sort() {
+        let mut hh = HhSketch::new(4);
+        for i in 0u64..8 {
+            let slice = [i];
+            // pairs of keys share a weight, producing ties
+            hh.update(slice.as_byte_slice(), i / 2 + 1);
+        }
+        let mut all = hh.estimate_no_fn();
+        all.sort_by(|a, b| {
+            b.ub.cmp(&a.ub)
+                .then_with(|| b.lb.cmp(&a.lb))
+                .then_with(|| a.key.cmp(b.key))
+        });
+        for k in 0..10 {
+            let expected: Vec<_> = all.iter().take(k).cloned().collect();
+            assert_eq!(hh.top_k(k, HhErrorType::NoFalseNegatives), expected);
+        }
+        let top: Vec<_> = hh
+            .top_k(3, HhErrorType::NoFalseNegatives)
+            .into_iter()
+            .map(|row| row.key.as_slice_of::<u64>().unwrap()[0])
+            .collect();
+        assert_eq!(top, vec![6, 7, 4]);
+    }
+
+    #[test]
+    fn top_k_order_independent() {
+        let keys: Vec<String> = (0..20).map(|i| format!("key{}", i)).collect();
+        let mut forward = HhSketch::new(5);
+        let mut backward = HhSketch::new(5);
+        // every key has the same weight, so all bounds tie
+        for key in &keys {
+            forward.update(key.as_bytes(), 3);
+        }
+        for key in keys.iter().rev() {
+            backward.update(key.as_bytes(), 3);
+        }
+        let forward = forward.top_k(5, HhErrorType::NoFalseNegatives);
+        let backward = backward.top_k(5, HhErrorType::NoFalseNegatives);
+        assert_eq!(forward, backward);
+        let mut sorted_keys: Vec<_> = keys.iter().map(|key| key.as_bytes()).collect();
+        sorted_keys.sort_unstable();
+        let top: Vec<_> = forward.iter().map(|row| row.key).collect();
+        assert_eq!(top, &sorted_keys[..5]);
+    }
+
+    #[test]
+    fn estimate_dispatch() {
+        let mut hh = HhSketch::new(3);
+        for i in 0u64..100 {
+            let slice = [i % 17];
+            hh.update(slice.as_byte_slice(), i % 7 + 1);
+        }
+        assert_eq!(hh.estimate(HhErrorType::NoFalsePositives), hh.estimate_no_fp());
+        assert_eq!(hh.estimate(HhErrorType::NoFalseNegatives), hh.estimate_no_fn());
+        assert_ne!(hh.estimate_no_fp(), hh.estimate_no_fn());
+    }
+
+    #[test]
+    fn key_hash_is_murmur3() {
+        // reference values of MurmurHash3_x64_128's first half
+        assert_eq!(key_hash(0, b""), 0);
+        assert_eq!(key_hash(0, b"hello"), 0xcbd8_a7b3_41bd_9b02);
+    }
+
+    #[test]
+    fn seeded_deterministic() {
+        // enough distinct keys to purge with sampling, since only up to
+        // 1024 items are sampled for the median
+        let lg2_k = 12;
+        let fill = |mut hh: HhSketch| {
+            for i in 0u64..(1 << 15) {
+                let slice = [i % 5000];
+                hh.update(slice.as_byte_slice(), i % 3 + 1);
+            }
+            hh
+        };
+        let a = fill(HhSketch::with_seed(lg2_k, 1234));
+        let b = fill(HhSketch::with_seed(lg2_k, 1234));
+        assert_eq!(a.estimate_no_fn(), b.estimate_no_fn());
+        assert_eq!(a.estimate_no_fp(), b.estimate_no_fp());
+        assert_eq!(a.inner.get_offset(), b.inner.get_offset());
+        assert!(a.inner.get_offset() > 0);
+
+        let c = fill(HhSketch::new(lg2_k));
+        let d = fill(HhSketch::new(lg2_k));
+        assert_eq!(c.estimate_no_fn(), d.estimate_no_fn());
+        check_cycle(&a);
+    }
+
+    #[test]
+    fn missing_removal_recorded() {
+        let mut hh = HhSketch::new(4);
+        hh.update(b"present", 1);
+        assert_eq!(hh.check_consistency(), Ok(()));
+
+        // simulate the C++ side evicting a key which was never interned
+        let stray = ThinByteBox(ThinBox::new(0, b"stray".iter().cloned()));
+        let stray_addr = ThinRef::<u64, u8>::erase(ThinRef::from(&*stray.0)).as_ptr() as usize;
+        let intern_addr = hh.intern.as_ref() as *const _ as usize;
+        unsafe { remove_from_hashset(intern_addr, stray_addr) };
+
+        assert_eq!(
+            hh.check_consistency(),
+            Err(DataSketchesError::Desync { missing_removals: 1 })
+        );
+        assert_eq!(hh.intern.keys.len(), 1);
+        hh.update(b"present", 1);
+        assert_eq!(hh.estimate_no_fp().len(), 1);
+    }
+
+    #[test]
+    fn merge_bounds_match_baseline() {
+        // heavy keys 0..4 appear in both halves, interspersed with many
+        // distinct light keys so that both halves purge
+        let lg2_k = 5;
+        let heavy = |i: u64| i % 4;
+        let half = |range: std::ops::Range<u64>| {
+            let mut hh = HhSketch::new(lg2_k);
+            let mut baseline = HhSketch::new(lg2_k);
+            for i in range {
+                for key in [heavy(i), 1000 + i].iter() {
+                    let slice = [*key];
+                    hh.update(slice.as_byte_slice(), 1);
+                    baseline.update(slice.as_byte_slice(), 1);
+                }
+            }
+            (hh, baseline)
+        };
+        let n = 4000;
+        let (mut merged, mut baseline) = half(0..n);
+        let (right, _) = half(n..2 * n);
+        for i in n..2 * n {
+            for key in [heavy(i), 1000 + i].iter() {
+                baseline.update([*key].as_byte_slice(), 1);
+            }
+        }
+        let offset = merged.inner.get_offset() + right.inner.get_offset();
+        assert!(offset > 0);
+        merged.merge(&right);
+
+        assert_eq!(merged.get_total_weight(), baseline.get_total_weight());
+        assert_eq!(merged.inner.get_offset(), offset);
+        let truth = (0..4).map(|k| (k, 2 * n / 4)).collect::<Vec<_>>();
+        matches(&merged, &truth);
+        matches(&baseline, &truth);
+        let merged_rows = row2keys(&merged);
+        let baseline_rows = row2keys(&baseline);
+        for k in 0..4 {
+            let (_, mlb, mub) = merged_rows.iter().find(|r| r.0 == k).unwrap();
+            let (_, blb, bub) = baseline_rows.iter().find(|r| r.0 == k).unwrap();
+            assert_eq!(mub - mlb, offset);
+            // merged intervals overlap the baseline ones around the truth
+            assert!(mlb <= bub && blb <= mub, "key {}", k);
+        }
+    }
+
+    #[test]
+    fn clone_is_exact() {
+        let mut hh = HhSketch::new(4);
+        for i in 0u64..1000 {
+            hh.update([i % 3].as_byte_slice(), 1);
+            hh.update([i].as_byte_slice(), i % 5 + 1);
+        }
+        assert!(hh.inner.get_offset() > 0);
+        let rows = |hh: &HhSketch| {
+            let rows = hh.estimate_no_fn().into_iter();
+            let mut rows: Vec<_> = rows.map(|row| (row.key.to_vec(), row.lb, row.ub)).collect();
+            rows.sort_unstable();
+            rows
+        };
+        let mut copy = hh.clone();
+        assert_eq!(rows(&copy), rows(&hh));
+        assert_eq!(copy.get_total_weight(), hh.get_total_weight());
+        assert_eq!(copy.inner.get_offset(), hh.inner.get_offset());
+        assert_eq!(copy.summary(), hh.summary());
+        assert!(copy.check_consistency().is_ok());
+
+        // the copy owns its keys, and evolves just as the original would
+        for i in 1000u64..2000 {
+            hh.update([i].as_byte_slice(), 2);
+            copy.update([i].as_byte_slice(), 2);
+        }
+        let expected = rows(&hh);
+        drop(hh);
+        assert_eq!(rows(&copy), expected);
+        assert!(copy.check_consistency().is_ok());
+    }
+
+    #[test]
+    fn serialize_roundtrip() {
+        let empty = HhSketch::with_seed(5, 7);
+        let copy = HhSketch::deserialize(&empty.serialize());
+        assert_eq!((copy.get_lg2_k(), copy.seed), (5, 7));
+        assert_eq!(copy.summary(), empty.summary());
+
+        // restored both while the table is still growing, and once it purges
+        let mut hh = HhSketch::new(6);
+        for (n, start) in [(10u64, 0u64), (3000, 10)].iter() {
+            for i in *start..start + n {
+                hh.update([i % 7].as_byte_slice(), 3);
+                hh.update([i].as_byte_slice(), i % 5 + 1);
+            }
+            let bytes = hh.serialize();
+            let mut copy = HhSketch::deserialize(&bytes);
+            assert_eq!(copy.serialize(), bytes);
+            assert_eq!(copy.estimate_no_fn(), hh.estimate_no_fn());
+            assert_eq!(copy.inner.get_offset(), hh.inner.get_offset());
+
+            // the copy's table is laid out alike, so it purges alike
+            let mut original = hh.clone();
+            for i in 5000u64..8000 {
+                original.update([i].as_byte_slice(), 1);
+                copy.update([i].as_byte_slice(), 1);
+            }
+            assert_eq!(copy.serialize(), original.serialize());
+            assert!(copy.check_consistency().is_ok());
+        }
+        assert!(hh.inner.get_offset() > 0);
+
+        let bytes = hh.serialize();
+        for len in &[0, 1, 17, bytes.len() - 1] {
+            assert!(HhSketch::try_deserialize(&bytes[..*len]).is_err(), "{}", len);
+        }
+        let mut too_small = bytes.clone();
+        too_small[1] = 3;
+        assert!(HhSketch::try_deserialize(&too_small).is_err());
+    }
+
+    #[test]
+    fn merge_three_partials_bounds() {
+        // the same heavy key with a different weight in each partial, each of
+        // which purges light keys, so that every partial has its own error
+        let heavy = [0u64];
+        let weights = [300, 500, 700];
+        let partials: Vec<_> = weights
+            .iter()
+            .enumerate()
+            .map(|(p, &weight)| {
+                let mut hh = HhSketch::new(4);
+                for i in 0..weight {
+                    hh.update(heavy.as_byte_slice(), 1);
+                    hh.update([1000 * (p as u64 + 1) + i].as_byte_slice(), 1);
+                }
+                assert!(hh.inner.get_offset() > 0);
+                hh
+            })
+            .collect();
+        let offsets: Vec<_> = partials.iter().map(|hh| hh.inner.get_offset()).collect();
+
+        let mut merged = partials[0].clone();
+        for hh in &partials[1..] {
+            merged.merge(hh);
+        }
+        let truth = weights.iter().sum::<u64>();
+        assert_eq!(merged.get_total_weight(), 2 * truth);
+        // purges while merging can only add to the partials' combined error
+        let offset = merged.inner.get_offset();
+        assert!(offset >= offsets.iter().sum::<u64>());
+        matches(&merged, &[(0, truth)]);
+        let (_, lb, ub) = row2keys(&merged).into_iter().find(|r| r.0 == 0).unwrap();
+        assert_eq!(ub - lb, offset);
+    }
+
+    #[test]
+    fn update_str_matches_bytes() {
+        let mut hh = HhSketch::new(4);
+        hh.update_str("hello", 2);
+        hh.update("hello".as_bytes(), 3);
+        assert_eq!(
+            hh.estimate_no_fp(),
+            vec![HhRow { key: b"hello", lb: 5, ub: 5 }]
+        );
+    }
+
+    #[test]
+    fn update_from_matches_update() {
+        let mut a = HhSketch::new(6);
+        let mut b = HhSketch::new(6);
+        for i in 0u64..1000 {
+            let key = (i % 37).to_string().repeat(10);
+            a.update(key.as_bytes(), i % 4);
+            b.update_from(key.into_bytes(), i % 4);
+        }
+        assert_eq!(a.estimate_no_fn(), b.estimate_no_fn());
+        assert_eq!(a.estimate_no_fp(), b.estimate_no_fp());
+        assert_eq!(a.intern.keys.len(), b.intern.keys.len());
+        check_cycle(&b);
+    }
+
+    #[test]
+    fn summary() {
+        let mut hh = HhSketch::new(5);
+        for i in 0u64..10 {
+            hh.update([i].as_byte_slice(), i + 1);
+        }
+        let summary = hh.summary();
+        assert_eq!(summary.lg2_k, hh.get_lg2_k());
+        assert_eq!(summary.total_weight, hh.get_total_weight());
+        assert_eq!(summary.total_weight, 55);
+        assert_eq!(summary.num_active_items, hh.inner.state().len());
+        assert_eq!(summary.num_active_items, 10);
+    }
+
+    #[test]
+    fn capacity_bounds_active_items() {
+        let mut hh = HhSketch::new(6);
+        assert_eq!(hh.capacity(), 48);
+        let mut max_active = 0;
+        for i in 0u64..1000 {
+            hh.update([i].as_byte_slice(), 1);
+            let active = hh.get_num_active_items();
+            assert!(active <= hh.capacity(), "{} > {}", active, hh.capacity());
+            assert_eq!(active as usize, hh.intern.keys.len());
+            max_active = max_active.max(active);
+        }
+        assert_eq!(max_active, hh.capacity());
+    }
+
+    #[test]
+    fn try_update_overflow() {
+        let mut hh = HhSketch::new(4);
+        hh.try_update(b"big", u64::MAX - 1).unwrap();
+        hh.try_update(b"small", 1).unwrap();
+        assert_eq!(hh.get_total_weight(), u64::MAX);
+        let err = hh.try_update(b"small", 1).unwrap_err();
+        assert_eq!(
+            err,
+            DataSketchesError::WeightOverflow {
+                total_weight: u64::MAX,
+                weight: 1
+            }
+        );
+        hh.try_update(b"new", 0).unwrap();
+        assert_eq!(hh.get_total_weight(), u64::MAX);
+        assert_eq!(hh.intern.keys.len(), 2);
+        let mut rows = hh.estimate_no_fp();
+        rows.sort_unstable();
+        assert_eq!(
+            rows,
+            vec![
+                HhRow { key: b"big", lb: u64::MAX - 1, ub: u64::MAX - 1 },
+                HhRow { key: b"small", lb: 1, ub: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn try_merge_overflow() {
+        // most of other's weight is evicted, so its rows alone fit
+        let mut other = HhSketch::new(4);
+        for i in 0u64..1000 {
+            other.update(&i.to_le_bytes(), 1);
+        }
+        let retained: u64 = other.estimate_no_fp().iter().map(|row| row.lb).sum();
+        assert!(retained < 500, "{}", retained);
+
+        let mut hh = HhSketch::new(4);
+        hh.update(b"big", u64::MAX - 500);
+        let err = hh.try_merge(&other).unwrap_err();
+        assert_eq!(
+            err,
+            DataSketchesError::WeightOverflow {
+                total_weight: u64::MAX - 500,
+                weight: 1000
+            }
+        );
+        assert_eq!(hh.get_total_weight(), u64::MAX - 500);
+        assert_eq!(hh.estimate_no_fp().len(), 1);
+
+        let mut hh = HhSketch::new(4);
+        hh.update(b"big", u64::MAX - 1000);
+        hh.try_merge(&other).unwrap();
+        assert_eq!(hh.get_total_weight(), u64::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "would overflow")]
+    fn update_overflow_panics() {
+        let mut hh = HhSketch::new(4);
+        hh.update(b"big", u64::MAX);
+        hh.update(b"big", 1);
+    }
+
+    #[test]
+    fn try_new_rejects_small_k() {
+        for lg2_k in 0..3 {
+            match HhSketch::try_new(lg2_k) {
+                Err(DataSketchesError::CppException(_)) => (),
+                other => panic!("lg2_k {} gave {:?}", lg2_k, other.map(|hh| hh.get_lg2_k())),
+            }
+        }
+        let mut hh = HhSketch::try_with_seed(3, 1).unwrap();
+        hh.update(b"key", 2);
+        assert_eq!(hh.estimate_no_fn().len(), 1);
+    }
+
+    #[test]
+    fn into_iter_no_fn() {
+        let mut hh = HhSketch::new(4);
+        for i in 0u64..100 {
+            hh.update([i % 20].as_byte_slice(), i % 7 + 1);
+        }
+        let mut rows = Vec::new();
+        for row in &hh {
+            rows.push(row);
+        }
+        assert_eq!(rows, hh.estimate_no_fn());
+        assert!(!rows.is_empty());
+    }
+
+    #[test]
+    fn estimate_above_threshold() {
+        let mut hh = HhSketch::new(5);
+        for i in 0u64..1000 {
+            hh.update(b"half", 1);
+            hh.update([i].as_byte_slice(), 1);
+        }
+        hh.update(b"few", 100);
+        let keys = |rows: Vec<HhRow<'_>>| {
+            rows.iter().map(|row| row.key.to_vec()).collect::<Vec<_>>()
+        };
+        for &error_type in &[HhErrorType::NoFalsePositives, HhErrorType::NoFalseNegatives] {
+            let rows = hh.estimate_above_threshold(0.4, error_type);
+            assert_eq!(keys(rows), vec![b"half".to_vec()]);
+            assert!(hh.estimate_above_threshold(0.5, error_type).is_empty());
+            let rows = hh.estimate_above_threshold(0.04, error_type);
+            assert_eq!(&keys(rows)[..2], &[b"half".to_vec(), b"few".to_vec()]);
+        }
+        let all = hh.estimate_above_threshold(f64::MIN_POSITIVE, HhErrorType::NoFalseNegatives);
+        assert_eq!(all.len(), hh.inner.state().len());
+        assert_eq!(all, hh.top_k(all.len(), HhErrorType::NoFalseNegatives));
+    }
+
+    #[test]
+    fn display_row() {
+        let row = HhRow {
+            key: "ключ key".as_bytes(),
+            lb: 3,
+            ub: 5,
+        };
+        assert_eq!(row.to_string(), "ключ key (3..5)");
+        let row = HhRow {
+            key: &[0xff, 0x00, b'a'],
+            lb: 0,
+            ub: 1,
+        };
+        assert_eq!(row.to_string(), "0xff0061 (0..1)");
+        assert_eq!(row.display_key().to_string(), "0xff0061");
+    }
 }
@@ -0,0 +1,23 @@
+//! Byte-identity keys for serialized sketches.
+
+/// The serialized bytes of a sketch, usable as a hash structure key. Two
+/// keys are equal exactly when the sketches serialized to the same bytes.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SketchKey(Vec<u8>);
+
+impl SketchKey {
+    pub(crate) fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    /// Return the serialized sketch this key was made from.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for SketchKey {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
@@ -0,0 +1,93 @@
+//! Iterator adaptors which feed sketches as items pass through.
+
+use crate::{CpcSketch, ThetaSketch};
+
+/// Extension methods attaching a sketch to an iterator of `u64`s, so that
+/// a stream's distinct count can be estimated without interrupting it.
+pub trait SketchExt: Iterator<Item = u64> + Sized {
+    /// Pass items through unchanged, updating `sketch` with each as by
+    /// [`CpcSketch::update_u64`]. Only items actually yielded are counted.
+    fn tee_cpc(self, sketch: &mut CpcSketch) -> TeeCpc<'_, Self> {
+        TeeCpc { iter: self, sketch }
+    }
+
+    /// Pass items through unchanged, updating `sketch` with each as by
+    /// [`ThetaSketch::update_u64`]. Only items actually yielded are counted.
+    fn tee_theta(self, sketch: &mut ThetaSketch) -> TeeTheta<'_, Self> {
+        TeeTheta { iter: self, sketch }
+    }
+}
+
+impl<I: Iterator<Item = u64>> SketchExt for I {}
+
+/// The iterator returned by [`SketchExt::tee_cpc`].
+pub struct TeeCpc<'a, I> {
+    iter: I,
+    sketch: &'a mut CpcSketch,
+}
+
+impl<I: Iterator<Item = u64>> Iterator for TeeCpc<'_, I> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let item = self.iter.next()?;
+        self.sketch.update_u64(item);
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// The iterator returned by [`SketchExt::tee_theta`].
+pub struct TeeTheta<'a, I> {
+    iter: I,
+    sketch: &'a mut ThetaSketch,
+}
+
+impl<I: Iterator<Item = u64>> Iterator for TeeTheta<'_, I> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let item = self.iter.next()?;
+        self.sketch.update_u64(item);
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tee_passes_items_through() {
+        let mut cpc = CpcSketch::new();
+        let mut theta = ThetaSketch::new();
+        let sum: u64 = (0..1000)
+            .map(|i| i % 500)
+            .tee_cpc(&mut cpc)
+            .filter(|i| i % 2 == 0)
+            .tee_theta(&mut theta)
+            .sum();
+        assert_eq!(sum, 2 * (0..500).filter(|i| i % 2 == 0).sum::<u64>());
+        let (lb, ub) = (cpc.get_lower_bound(2), cpc.get_upper_bound(2));
+        assert!(lb <= 500.0 && 500.0 <= ub, "[{}, {}]", lb, ub);
+        assert_eq!(theta.estimate(), 250.0);
+    }
+
+    #[test]
+    fn tee_counts_only_consumed_items() {
+        let mut theta = ThetaSketch::new();
+        {
+            let mut iter = (0..1000).tee_theta(&mut theta);
+            assert_eq!(iter.size_hint(), (1000, Some(1000)));
+            assert_eq!(iter.nth(9), Some(9));
+        }
+        assert_eq!(theta.estimate(), 10.0);
+    }
+}
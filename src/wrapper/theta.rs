@@ -1,8 +1,13 @@
 //! Wrapper types for the Theta sketch.
 
-use cxx;
+use serde::Serialize;
+
+use std::convert::TryInto;
 
 use crate::bridge::ffi;
+use crate::wrapper::flatten;
+use crate::wrapper::round_estimate;
+use crate::DataSketchesError;
 
 /// The [Theta][orig-docs] sketch is, essentially, an adaptive random sample
 /// of a stream. As a result, it can be used to estimate distinct counts and
@@ -30,26 +35,136 @@ impl ThetaSketch {
         }
     }
 
+    /// Build a sketch of `values`, e.g., the contents of a set of IDs, as if
+    /// by calling [`update_u64`](Self::update_u64) on each on a new sketch.
+    pub fn from_u64_set(values: &[u64]) -> Self {
+        let mut sketch = Self::new();
+        sketch.update_u64_slice(values);
+        sketch
+    }
+
+    /// Create a Theta sketch representing the empty set, which retains
+    /// about `2^lg_k` hashes and only considers each distinct value with
+    /// probability `p`, decided by its hash. Estimates account for `p`, which
+    /// saves space for enormous streams at the cost of accuracy.
+    /// `lg_k` must be in `5..=26` and `p` in `(0, 1]`.
+    pub fn with_p(lg_k: u8, p: f32) -> Self {
+        assert!((5..=26).contains(&lg_k), "lg_k {} not in 5..=26", lg_k);
+        assert!(p > 0.0 && p <= 1.0, "p {} not in (0, 1]", p);
+        Self {
+            inner: ffi::new_opaque_theta_sketch_with_p(lg_k, p).expect("lg_k and p checked"),
+        }
+    }
+
     /// Return the current estimate of distinct values seen.
     pub fn estimate(&self) -> f64 {
         self.inner.estimate()
     }
 
+    /// Return the estimate of distinct values seen, rounded half-to-even
+    /// and saturating at `u64::MAX`.
+    pub fn estimate_rounded(&self) -> u64 {
+        round_estimate(self.estimate())
+    }
+
+    /// Return whether the sketch has seen no values. Unlike the estimate,
+    /// this is exact. There is no counterpart returning the number of
+    /// updates, since distinct counting sketches don't track it.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Return the number of bytes the sketch's variable-size buffers currently
+    /// occupy on the C++ heap. This excludes the fixed-size sketch object itself.
+    pub fn get_allocated_bytes(&self) -> usize {
+        self.inner.get_allocated_bytes()
+    }
+
     /// Observe a new value. Two values must have the exact same
     /// bytes and lengths to be considered equal.
     pub fn update(&mut self, value: &[u8]) {
         self.inner.pin_mut().update(value)
     }
 
+    /// Observe each of `keys`, equivalent to calling `update()` on each in
+    /// turn, but crossing into C++ once for the whole batch.
+    pub fn update_batch(&mut self, keys: &[&[u8]]) {
+        let (buf, ends) = flatten(keys);
+        self.inner.pin_mut().update_batch(&buf, &ends)
+    }
+
+    /// Apply any buffered updates. Every update method, including the
+    /// batched ones, applies its values to the C++ sketch before returning,
+    /// so this currently does nothing and is never necessary. It marks the
+    /// points where code which feeds the sketch in chunks relies on all
+    /// prior updates being reflected, e.g., by [`Self::estimate`], should
+    /// updates ever be deferred.
+    pub fn flush(&mut self) {}
+
+    /// Observe a new string, equivalent to calling `update()` on its UTF-8 bytes.
+    pub fn update_str(&mut self, value: &str) {
+        self.update(value.as_bytes())
+    }
+
     /// Observe a new `u64`. If the native-endian byte ordered bytes
     /// are equal to any other value seen by `update()`, this will be considered
     /// equal. If you are intending to use serialized sketches across
     /// platforms with different endianness, make sure to convert this
-    /// `value` to network order first.
+    /// `value` to network order first, or use
+    /// [`update_u64_be`](Self::update_u64_be) instead.
     pub fn update_u64(&mut self, value: u64) {
         self.inner.pin_mut().update_u64(value)
     }
 
+    /// Observe each of `values` in turn, as [`update_u64`](Self::update_u64)
+    /// would, but with a single call into C++ for the whole slice.
+    pub fn update_u64_slice(&mut self, values: &[u64]) {
+        self.inner.pin_mut().update_u64_slice(values)
+    }
+
+    /// Observe a new `u64` by its big-endian (network order) bytes, so that
+    /// the resulting sketch is the same on every platform. This is
+    /// equivalent to `update(&value.to_be_bytes())`, and matches
+    /// [`update_u64`](Self::update_u64) only on big-endian platforms.
+    pub fn update_u64_be(&mut self, value: u64) {
+        self.update(&value.to_be_bytes())
+    }
+
+    /// Return the sketch to its empty state, as if newly created with the
+    /// same parameters.
+    pub fn reset(&mut self) {
+        self.inner.pin_mut().reset()
+    }
+
+    /// Update this sketch with the hashes retained by `other`, as if the
+    /// values behind them had been passed to [`Self::update`], without
+    /// constructing a [`ThetaUnion`].
+    ///
+    /// The resulting theta is the minimum of the two, so if `other` is in
+    /// estimation mode, hashes this sketch retained above its theta are
+    /// dropped and the estimate has the accuracy of the coarser sketch.
+    ///
+    /// Panics if datasketches-cpp rejects `other`; see [`Self::try_absorb`].
+    pub fn absorb(&mut self, other: &StaticThetaSketch) {
+        self.try_absorb(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Absorb `other` as [`Self::absorb`] does, returning an error if
+    /// datasketches-cpp rejects it, e.g., when it was hashed with a
+    /// different seed.
+    pub fn try_absorb(&mut self, other: &StaticThetaSketch) -> Result<(), DataSketchesError> {
+        self.inner
+            .pin_mut()
+            .absorb(other.inner.as_ref().expect("non-null"))?;
+        Ok(())
+    }
+
+    /// Return an immutable copy of the sketch, whose retained hashes are
+    /// sorted. While the sketch is in exact mode its retained hashes are
+    /// exactly those of the values seen, so the copy serializes to the same
+    /// bytes regardless of update order. In estimation mode, theta is set
+    /// by rebuilds of the hash table, and which hashes were retained when
+    /// they happened depends on the order.
     pub fn as_static(&self) -> StaticThetaSketch {
         StaticThetaSketch {
             inner: self.inner.as_static(),
@@ -57,6 +172,15 @@ impl ThetaSketch {
     }
 }
 
+impl Default for ThetaSketch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An immutable Theta sketch, which supports set operations. Static
+/// sketches are `Send` and `Sync`, so one may be read from many threads,
+/// e.g., behind an `Arc`.
 pub struct StaticThetaSketch {
     inner: cxx::UniquePtr<ffi::OpaqueStaticThetaSketch>,
 }
@@ -67,6 +191,59 @@ impl StaticThetaSketch {
         self.inner.estimate()
     }
 
+    /// Return the estimate of distinct values seen, rounded half-to-even
+    /// and saturating at `u64::MAX`.
+    pub fn estimate_rounded(&self) -> u64 {
+        round_estimate(self.estimate())
+    }
+
+    /// Return whether the sketch represents the empty set. This is exact,
+    /// and also holds for, e.g., set differences that removed everything.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Return whether the retained hashes are sorted. Sketches produced by
+    /// this crate always are, but deserialized ones need not be.
+    pub fn is_ordered(&self) -> bool {
+        self.inner.is_ordered()
+    }
+
+    /// Return the sampling threshold theta in `(0, 1]`, the fraction of the
+    /// hash space the retained hashes are drawn from. It is 1 while the
+    /// sketch is exact, and shrinks as more distinct values are seen.
+    pub fn get_theta(&self) -> f64 {
+        self.inner.get_theta()
+    }
+
+    /// Return the number of hashes retained, from which the estimate is
+    /// `get_num_retained() / get_theta()`.
+    pub fn get_num_retained(&self) -> u32 {
+        self.inner.get_num_retained()
+    }
+
+    /// Return the number of bytes the sketch's variable-size buffers currently
+    /// occupy on the C++ heap. This excludes the fixed-size sketch object itself.
+    pub fn get_allocated_bytes(&self) -> usize {
+        self.inner.get_allocated_bytes()
+    }
+
+    /// Return the sketch's key parameters and current state, e.g., for logging.
+    pub fn summary(&self) -> StaticThetaSummary {
+        StaticThetaSummary {
+            estimate: self.estimate(),
+            is_ordered: self.is_ordered(),
+            serialized_bytes: self.serialize().as_ref().len(),
+        }
+    }
+
+    /// Return whether the sketch is in compact form. This is always true,
+    /// since a static sketch wraps the C++ `compact_theta_sketch`; it is
+    /// provided for parity with the datasketches API.
+    pub fn is_compact(&self) -> bool {
+        true
+    }
+
     /// Return the sketch representing the set of elements present
     /// in `self` without any of the elements also present in `other`.
     pub fn set_difference(&mut self, other: &StaticThetaSketch) {
@@ -85,16 +262,171 @@ impl StaticThetaSketch {
         UPtrVec(self.inner.serialize())
     }
 
-    pub fn deserialize(buf: &[u8]) -> Self {
-        // TODO: this could be friendlier, it currently terminates
-        // the program no bad deserialization, and instead can be a
-        // Result.
-        Self {
-            inner: ffi::deserialize_opaque_static_theta_sketch(buf),
+    /// Like [`serialize`](Self::serialize), but copies the bytes into an
+    /// owned `Vec<u8>` that can be stored or sent across threads.
+    pub fn serialize_to_vec(&self) -> Vec<u8> {
+        self.inner.serialize().as_slice().to_vec()
+    }
+
+    /// Like [`serialize_to_vec`](Self::serialize_to_vec), but consumes the
+    /// sketch, freeing it before the bytes are copied out so that it's
+    /// never alive alongside the owned serialization.
+    pub fn into_bytes(self) -> Vec<u8> {
+        let serialized = self.inner.serialize();
+        std::mem::drop(self);
+        serialized.as_slice().to_vec()
+    }
+
+    /// Deserialize a sketch written by [`Self::serialize`], panicking if
+    /// the buffer is malformed; see [`Self::try_deserialize`].
+    pub fn deserialize<B: AsRef<[u8]>>(buf: B) -> Self {
+        Self::try_deserialize(buf).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Deserialize a sketch written by [`Self::serialize`], returning an
+    /// error if datasketches-cpp rejects the buffer. Unlike [`Self::wrap`],
+    /// the seed and hashes are checked by the C++ side. Buffers from a newer
+    /// serialization format are rejected with
+    /// [`DataSketchesError::UnsupportedVersion`] before reaching C++.
+    pub fn try_deserialize<B: AsRef<[u8]>>(buf: B) -> Result<Self, DataSketchesError> {
+        let buf = buf.as_ref();
+        let family = buf.get(2);
+        if let (Some(&version), Some(&ThetaWrapped::COMPACT_SKETCH_TYPE)) = (buf.get(1), family) {
+            if version > ThetaWrapped::SERIAL_VERSION {
+                return Err(DataSketchesError::UnsupportedVersion {
+                    family: "theta",
+                    version,
+                });
+            }
         }
+        Ok(Self {
+            inner: ffi::deserialize_opaque_static_theta_sketch(buf)?,
+        })
+    }
+
+    /// Read a sketch serialized by [`StaticThetaSketch::serialize`] in place,
+    /// without copying its retained hashes into a new C++ sketch. The
+    /// preamble is validated up front, so malformed buffers result in an
+    /// error.
+    pub fn wrap(buf: &[u8]) -> Result<ThetaWrapped<'_>, DataSketchesError> {
+        ThetaWrapped::new(buf)
     }
 }
 
+/// A read-only view of a serialized [`StaticThetaSketch`], borrowing the
+/// buffer it was read from, see [`StaticThetaSketch::wrap`].
+///
+/// This parses the compact theta serialization format directly, so only
+/// the information in its preamble is available without copying.
+#[derive(Clone, Copy, Debug)]
+pub struct ThetaWrapped<'a> {
+    buf: &'a [u8],
+    is_empty: bool,
+    num_retained: u32,
+    theta: u64,
+}
+
+impl<'a> ThetaWrapped<'a> {
+    const SERIAL_VERSION: u8 = 3;
+    const COMPACT_SKETCH_TYPE: u8 = 3;
+    const IS_EMPTY_FLAG: u8 = 1 << 2;
+    const IS_COMPACT_FLAG: u8 = 1 << 3;
+    /// The seed hash of the default seed, which all sketches here use.
+    const SEED_HASH: u16 = 0x93cc;
+    const MAX_THETA: u64 = i64::MAX as u64;
+
+    fn new(buf: &'a [u8]) -> Result<Self, DataSketchesError> {
+        let invalid = |msg: String| Err(DataSketchesError::InvalidBuffer(msg));
+        let u64_at = |i: usize| u64::from_le_bytes(buf[i..i + 8].try_into().unwrap());
+        if buf.len() < 8 {
+            return invalid(format!(
+                "{} bytes is too short for a theta sketch",
+                buf.len()
+            ));
+        }
+        let (preamble_longs, serial_version, sketch_type, flags) = (buf[0], buf[1], buf[2], buf[5]);
+        if sketch_type != Self::COMPACT_SKETCH_TYPE || flags & Self::IS_COMPACT_FLAG == 0 {
+            return invalid("not a compact theta sketch".to_owned());
+        }
+        if serial_version > Self::SERIAL_VERSION {
+            return Err(DataSketchesError::UnsupportedVersion {
+                family: "theta",
+                version: serial_version,
+            });
+        }
+        if serial_version != Self::SERIAL_VERSION {
+            return invalid(format!(
+                "unsupported theta serial version {}",
+                serial_version
+            ));
+        }
+        let is_empty = flags & Self::IS_EMPTY_FLAG != 0;
+        let seed_hash = u16::from_le_bytes([buf[6], buf[7]]);
+        if !is_empty && seed_hash != Self::SEED_HASH {
+            return invalid(format!("unexpected seed hash {:#x}", seed_hash));
+        }
+        let (num_retained, theta) = match (is_empty, preamble_longs) {
+            (true, _) => (0, Self::MAX_THETA),
+            (false, 1) => (1, Self::MAX_THETA),
+            (false, 2) | (false, 3) if buf.len() >= 8 * preamble_longs as usize => {
+                let num_retained = u32::from_le_bytes(buf[8..12].try_into().unwrap());
+                let theta = if preamble_longs == 3 {
+                    u64_at(16)
+                } else {
+                    Self::MAX_THETA
+                };
+                (num_retained, theta)
+            }
+            _ => return invalid(format!("bad preamble length {}", preamble_longs)),
+        };
+        let expected_len = 8 * (preamble_longs as usize + num_retained as usize);
+        if !is_empty && buf.len() < expected_len {
+            return invalid(format!(
+                "{} bytes is too short for {} retained hashes",
+                buf.len(),
+                num_retained
+            ));
+        }
+        Ok(Self {
+            buf,
+            is_empty,
+            num_retained,
+            theta,
+        })
+    }
+
+    /// Return the estimate of distinct values seen, equal to that of the
+    /// wrapped sketch once deserialized.
+    pub fn estimate(&self) -> f64 {
+        self.num_retained as f64 / (self.theta as f64 / Self::MAX_THETA as f64)
+    }
+
+    /// Return whether the sketch represents the empty set.
+    pub fn is_empty(&self) -> bool {
+        self.is_empty
+    }
+
+    /// Deserialize the wrapped buffer into an owned sketch.
+    pub fn to_static(&self) -> StaticThetaSketch {
+        StaticThetaSketch::deserialize(self.buf)
+    }
+}
+
+/// A snapshot of a [`StaticThetaSketch`], see [`StaticThetaSketch::summary`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StaticThetaSummary {
+    pub estimate: f64,
+    pub is_ordered: bool,
+    pub serialized_bytes: usize,
+}
+
+// The C++ compact sketch owns all of its state and has no thread affinity.
+// Its const methods, which are all that `&self` methods call, only read that
+// state, without internal caching or other mutation, so they may run
+// concurrently.
+unsafe impl Send for StaticThetaSketch {}
+unsafe impl Sync for StaticThetaSketch {}
+
 impl Clone for StaticThetaSketch {
     fn clone(&self) -> Self {
         Self {
@@ -116,8 +448,17 @@ impl ThetaUnion {
         }
     }
 
+    /// Merge `sketch` into the union, panicking if datasketches-cpp rejects
+    /// it; see [`Self::try_merge`].
     pub fn merge(&mut self, sketch: StaticThetaSketch) {
-        self.inner.pin_mut().union_with(sketch.inner)
+        self.try_merge(sketch).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Merge `sketch` into the union, returning an error if datasketches-cpp
+    /// rejects it, e.g., when the sketch was hashed with a different seed.
+    pub fn try_merge(&mut self, sketch: StaticThetaSketch) -> Result<(), DataSketchesError> {
+        self.inner.pin_mut().union_with(sketch.inner)?;
+        Ok(())
     }
 
     /// Retrieve the current unioned sketch as a copy.
@@ -126,8 +467,38 @@ impl ThetaUnion {
             inner: self.inner.sketch(),
         }
     }
+
+    /// Return the sampling threshold of the current unioned sketch, see
+    /// [`StaticThetaSketch::get_theta`].
+    ///
+    /// Both theta and the retained count depend on trimming the union's
+    /// table down to its nominal size, which datasketches-cpp only does
+    /// when building the result, so this builds the full copy that
+    /// [`Self::sketch`] returns. To read several values at once, call
+    /// [`Self::sketch`] once and read them from its result instead.
+    pub fn current_theta(&self) -> f64 {
+        self.sketch().get_theta()
+    }
+
+    /// Return the number of hashes retained by the current unioned sketch,
+    /// see [`StaticThetaSketch::get_num_retained`]. Like
+    /// [`Self::current_theta`], this copies the union's result.
+    pub fn current_num_retained(&self) -> u32 {
+        self.sketch().get_num_retained()
+    }
+}
+
+impl Default for ThetaUnion {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
+/// An intersection of static theta sketches. Intersecting nothing leaves the
+/// universal set, containing every item, which no sketch can represent; so
+/// until the first [`merge`](Self::merge) the intersection is universal and
+/// has no [`sketch`](Self::sketch). Once anything is merged, the result is
+/// an ordinary, possibly empty, sketch.
 pub struct ThetaIntersection {
     inner: cxx::UniquePtr<ffi::OpaqueThetaIntersection>,
 }
@@ -140,8 +511,19 @@ impl ThetaIntersection {
         }
     }
 
+    /// Intersect the current result with `sketch`, panicking if
+    /// datasketches-cpp rejects it; see [`Self::try_merge`].
     pub fn merge(&mut self, sketch: StaticThetaSketch) {
-        self.inner.pin_mut().intersect_with(sketch.inner);
+        self.try_merge(sketch).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Intersect the current result with `sketch`, returning an error if
+    /// datasketches-cpp rejects it, e.g., when the sketch was hashed with a
+    /// different seed or holds duplicate hashes, which a corrupted buffer may
+    /// deserialize to. The intersection may be left in an intermediate state.
+    pub fn try_merge(&mut self, sketch: StaticThetaSketch) -> Result<(), DataSketchesError> {
+        self.inner.pin_mut().intersect_with(sketch.inner)?;
+        Ok(())
     }
 
     /// Retrieve the current intersected sketch as a copy. Returns `None`
@@ -152,6 +534,19 @@ impl ThetaIntersection {
         let valid = !inner.is_null();
         valid.then(|| StaticThetaSketch { inner })
     }
+
+    /// Return whether the intersection still represents the universal set,
+    /// i.e., nothing has been merged into it. This is exactly when
+    /// [`sketch`](Self::sketch) returns `None`, but doesn't copy the result.
+    pub fn is_universal(&self) -> bool {
+        !self.inner.has_result()
+    }
+}
+
+impl Default for ThetaIntersection {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
@@ -196,6 +591,165 @@ mod tests {
         assert!((lb..ub).contains(&est2));
     }
 
+    #[test]
+    fn reset() {
+        let mut theta = ThetaSketch::with_p(10, 0.5);
+        for key in 0u64..10000 {
+            theta.update_u64(key);
+        }
+        theta.reset();
+        assert_eq!(theta.estimate(), 0.0);
+        let mut fresh = ThetaSketch::with_p(10, 0.5);
+        for key in 20000u64..25000 {
+            theta.update_u64(key);
+            fresh.update_u64(key);
+        }
+        assert_eq!(theta.estimate(), fresh.estimate());
+        assert_eq!(
+            theta.as_static().serialize().as_ref(),
+            fresh.as_static().serialize().as_ref()
+        );
+    }
+
+    #[test]
+    fn is_empty() {
+        let mut theta = ThetaSketch::new();
+        assert!(theta.is_empty());
+        assert!(theta.as_static().is_empty());
+        theta.update(b"");
+        assert!(!theta.is_empty());
+        let mut s = theta.as_static();
+        assert!(!s.is_empty());
+        assert!(!StaticThetaSketch::deserialize(s.serialize()).is_empty());
+        s.set_difference(&theta.as_static());
+        assert!(s.is_empty());
+        theta.reset();
+        assert!(theta.is_empty());
+
+        // sampling drops values, but not the fact that some were seen
+        let mut sampled = ThetaSketch::with_p(10, 0.01);
+        sampled.update_u64(1);
+        assert!(!sampled.is_empty());
+    }
+
+    #[test]
+    fn wrap() {
+        let mut theta = ThetaSketch::with_p(12, 0.5);
+        let check = |theta: &ThetaSketch| {
+            let bytes = theta.as_static().serialize_to_vec();
+            let wrapped = StaticThetaSketch::wrap(&bytes).unwrap();
+            let owned = StaticThetaSketch::deserialize(&bytes);
+            assert_eq!(wrapped.estimate(), owned.estimate());
+            assert_eq!(wrapped.is_empty(), owned.is_empty());
+            assert_eq!(wrapped.to_static().serialize_to_vec(), bytes);
+        };
+        check(&theta);
+        theta.update_u64(1);
+        check(&theta);
+        let mut exact = ThetaSketch::new();
+        exact.update_u64(1);
+        exact.update_u64(2);
+        check(&exact);
+        for key in 0u64..100000 {
+            theta.update_u64(key);
+        }
+        check(&theta);
+    }
+
+    #[test]
+    fn wrap_rejects_malformed() {
+        let mut theta = ThetaSketch::new();
+        for key in 0u64..100000 {
+            theta.update_u64(key);
+        }
+        let bytes = theta.as_static().serialize_to_vec();
+        let mut cpc = crate::CpcSketch::new();
+        cpc.update_u64(1);
+        let bad: &[&[u8]] = &[
+            &[],
+            &bytes[..7],
+            &bytes[..bytes.len() - 1],
+            &cpc.serialize_to_vec(),
+            &[
+                bytes[0], 2, bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+            ],
+            &[bytes[0], 3, bytes[2], bytes[3], bytes[4], bytes[5], 0, 0],
+        ];
+        for buf in bad {
+            match StaticThetaSketch::wrap(buf) {
+                Err(DataSketchesError::InvalidBuffer(_)) => (),
+                other => panic!("{:?} for {:?}", other.map(|w| w.estimate()), buf),
+            }
+        }
+    }
+
+    #[test]
+    fn try_deserialize_malformed() {
+        let mut theta = ThetaSketch::new();
+        theta.update_u64(1);
+        let bytes = theta.as_static().serialize_to_vec();
+        let bad: &[&[u8]] = &[&bytes[..4], b"not a sketch"];
+        for buf in bad {
+            match StaticThetaSketch::try_deserialize(buf) {
+                Err(DataSketchesError::CppException(_)) => (),
+                other => panic!("{:?} for {:?}", other.map(|t| t.estimate()), buf),
+            }
+        }
+        let theta = StaticThetaSketch::try_deserialize(&bytes).unwrap();
+        assert_eq!(theta.estimate(), 1.0);
+    }
+
+    #[test]
+    fn try_deserialize_newer_version() {
+        let mut theta = ThetaSketch::new();
+        theta.update_u64(1);
+        let mut bytes = theta.as_static().serialize_to_vec();
+        bytes[1] = 255;
+        match StaticThetaSketch::try_deserialize(&bytes) {
+            Err(e @ DataSketchesError::UnsupportedVersion { version: 255, .. }) => {
+                assert_eq!(e.to_string(), "unsupported theta serial version 255")
+            }
+            other => panic!("{:?}", other.map(|t| t.estimate())),
+        }
+        match StaticThetaSketch::wrap(&bytes) {
+            Err(DataSketchesError::UnsupportedVersion { version: 255, .. }) => (),
+            other => panic!("{:?}", other.map(|t| t.estimate())),
+        }
+    }
+
+    #[test]
+    fn union_theta_decreases() {
+        let mut union = ThetaUnion::new();
+        assert_eq!(union.current_theta(), 1.0);
+        assert_eq!(union.current_num_retained(), 0);
+        let mut last_theta = 1.0;
+        for i in 0u64..20 {
+            let mut theta = ThetaSketch::new();
+            for key in i * 2000..(i + 1) * 2000 {
+                theta.update_u64(key);
+            }
+            union.merge(theta.as_static());
+            let (current, retained) = (union.current_theta(), union.current_num_retained());
+            assert!(current <= last_theta, "theta {} after {}", current, last_theta);
+            let sketch = union.sketch();
+            assert_eq!(sketch.get_theta(), current);
+            assert_eq!(sketch.get_num_retained(), retained);
+            assert_eq!(sketch.estimate(), retained as f64 / current);
+            last_theta = current;
+        }
+        assert!(last_theta < 1.0);
+    }
+
+    #[test]
+    fn defaults() {
+        let theta: ThetaSketch = Default::default();
+        assert_eq!(theta.estimate(), 0.0);
+        let union: ThetaUnion = Default::default();
+        assert_eq!(union.sketch().estimate(), 0.0);
+        let intersection: ThetaIntersection = Default::default();
+        assert!(intersection.sketch().is_none());
+    }
+
     #[test]
     fn basic_count_distinct() {
         let mut slice = [0u64];
@@ -238,6 +792,49 @@ mod tests {
         }
     }
 
+    #[test]
+    fn intersect_universal() {
+        let mut intersection = ThetaIntersection::new();
+        assert!(intersection.is_universal());
+        assert!(intersection.sketch().is_none());
+        // even intersecting with the empty set leaves a result, if an empty one
+        intersection.merge(ThetaSketch::new().as_static());
+        assert!(!intersection.is_universal());
+        assert!(intersection.sketch().expect("non-inf").is_empty());
+    }
+
+    /// Serializes a two-item sketch, then overwrites its second hash with its
+    /// first, which deserializes but trips the intersection's sanity checks.
+    fn duplicate_hash_sketch() -> StaticThetaSketch {
+        let mut bytes = ThetaSketch::from_u64_set(&[1, 2]).as_static().serialize_to_vec();
+        assert_eq!(bytes.len(), 32);
+        let (head, tail) = bytes.split_at_mut(24);
+        tail.copy_from_slice(&head[16..]);
+        StaticThetaSketch::try_deserialize(&bytes).expect("deserializes")
+    }
+
+    #[test]
+    fn try_merge_rejects_corrupt() {
+        let mut intersection = ThetaIntersection::new();
+        match intersection.try_merge(duplicate_hash_sketch()) {
+            Err(DataSketchesError::CppException(msg)) => {
+                assert!(msg.contains("duplicate"), "{}", msg)
+            }
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+
+        let mut union = ThetaUnion::new();
+        union.try_merge(duplicate_hash_sketch()).unwrap();
+        union.try_merge(ThetaSketch::from_u64_set(&[3]).as_static()).unwrap();
+        assert_eq!(union.sketch().estimate(), 2.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate key")]
+    fn merge_panics_on_corrupt() {
+        ThetaIntersection::new().merge(duplicate_hash_sketch());
+    }
+
     #[test]
     fn basic_intersect() {
         let mut slice = [0u64];
@@ -270,4 +867,281 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn bulk_u64_matches_update() {
+        let values: Vec<u64> = (0..1_000_000).map(|i| i * 7 % 300_000).collect();
+        let mut looped = ThetaSketch::new();
+        for &value in &values {
+            looped.update_u64(value);
+        }
+        let bulk = ThetaSketch::from_u64_set(&values);
+        assert_eq!(bulk.estimate(), looped.estimate());
+        assert_eq!(bulk.as_static().serialize().as_ref(), looped.as_static().serialize().as_ref());
+        assert!(ThetaSketch::from_u64_set(&[]).is_empty());
+    }
+
+    #[test]
+    fn flush_between_batches() {
+        // small enough that the sketch stays exact, so estimates only grow
+        let keys: Vec<String> = (0..4000).map(|i| format!("key{}", i % 3000)).collect();
+        let keys: Vec<&[u8]> = keys.iter().map(|key| key.as_bytes()).collect();
+        let mut theta = ThetaSketch::new();
+        let mut last = 0.0;
+        for chunk in keys.chunks(250) {
+            theta.update_batch(chunk);
+            theta.flush();
+            let estimate = theta.estimate();
+            assert!(estimate >= last, "{} after {}", estimate, last);
+            last = estimate;
+        }
+        assert_eq!(last, 3000.0);
+    }
+
+    #[test]
+    fn update_str_matches_bytes() {
+        let mut a = ThetaSketch::new();
+        let mut b = ThetaSketch::new();
+        a.update_str("hello");
+        b.update("hello".as_bytes());
+        assert_eq!(
+            a.as_static().serialize().as_ref(),
+            b.as_static().serialize().as_ref()
+        );
+    }
+
+    #[test]
+    fn static_is_compact_ordered() {
+        let mut theta = ThetaSketch::new();
+        for key in 0u64..10000 {
+            theta.update_u64(key);
+        }
+        let s = theta.as_static();
+        assert!(s.is_ordered());
+        assert!(s.is_compact());
+        let cpy = StaticThetaSketch::deserialize(s.serialize().as_ref());
+        assert!(cpy.is_ordered());
+        assert!(cpy.is_compact());
+    }
+
+    #[test]
+    fn estimate_rounded() {
+        for n in [0u64, 10, 100_000].iter().cloned() {
+            let mut theta = ThetaSketch::new();
+            theta.update_u64_slice(&(0..n).collect::<Vec<_>>());
+            assert_eq!(theta.estimate_rounded(), theta.estimate().round() as u64);
+            let s = theta.as_static();
+            assert_eq!(s.estimate_rounded(), s.estimate().round() as u64);
+        }
+    }
+
+    #[test]
+    fn static_bytes_ignore_order() {
+        let orders = |n: u64| {
+            let mut forward = ThetaSketch::new();
+            forward.update_u64_slice(&(0..n).collect::<Vec<_>>());
+            let mut backward = ThetaSketch::new();
+            backward.update_u64_slice(&(0..n).rev().collect::<Vec<_>>());
+            (forward.as_static(), backward.as_static())
+        };
+
+        let (forward, backward) = orders(1000);
+        assert_eq!(forward.get_theta(), 1.0);
+        assert_eq!(forward.serialize().as_ref(), backward.serialize().as_ref());
+
+        // once rebuilds start, which hashes survive depends on the order
+        let (forward, backward) = orders(100_000);
+        assert!(forward.get_theta() < 1.0);
+        assert_ne!(forward.serialize().as_ref(), backward.serialize().as_ref());
+    }
+
+    #[test]
+    fn sampled_estimate() {
+        let n = 1000 * 1000;
+        let mut theta = ThetaSketch::with_p(12, 0.1);
+        for key in 0u64..n {
+            theta.update_u64(key);
+        }
+        check_cycle(&theta);
+        let est = theta.estimate();
+        let lb = n as f64 * 0.95;
+        let ub = n as f64 * 1.05;
+        assert!((lb..ub).contains(&est), "est {}", est);
+    }
+
+    #[test]
+    fn allocated_bytes() {
+        let mut theta = ThetaSketch::new();
+        let empty = theta.get_allocated_bytes();
+        for key in 0u64..1000 {
+            theta.update_u64(key);
+        }
+        let full = theta.get_allocated_bytes();
+        assert!(full > empty);
+        let s = theta.as_static();
+        assert!(s.get_allocated_bytes() < full);
+        assert!(s.get_allocated_bytes() >= 1000 * 8);
+    }
+
+    #[test]
+    fn concurrent_reads() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let mut theta = ThetaSketch::new();
+        for key in 0u64..100000 {
+            theta.update_u64(key);
+        }
+        let s = Arc::new(theta.as_static());
+        let est = s.estimate();
+        let bytes = s.serialize_to_vec();
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let s = Arc::clone(&s);
+                thread::spawn(move || {
+                    (0..100)
+                        .map(|_| (s.estimate(), s.serialize_to_vec()))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for handle in handles {
+            for (e, b) in handle.join().unwrap() {
+                assert_eq!(e, est);
+                assert_eq!(b, bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn summary() {
+        let mut theta = ThetaSketch::new();
+        for key in 0u64..1000 {
+            theta.update_u64(key);
+        }
+        let s = theta.as_static();
+        let summary = s.summary();
+        assert_eq!(summary.estimate, s.estimate());
+        assert_eq!(summary.is_ordered, s.is_ordered());
+        assert_eq!(summary.serialized_bytes, s.serialize().as_ref().len());
+    }
+
+    #[test]
+    fn serialize_to_vec_roundtrip() {
+        let mut theta = ThetaSketch::new();
+        for key in 0u64..10000 {
+            theta.update_u64(key);
+        }
+        let s = theta.as_static();
+        let bytes = s.serialize_to_vec();
+        assert_eq!(bytes, s.serialize().as_ref());
+        let copy = StaticThetaSketch::deserialize(&bytes);
+        assert_eq!(copy.serialize_to_vec(), bytes);
+        assert_eq!(copy.estimate(), s.estimate());
+    }
+
+    #[test]
+    fn absorb_approximates_union() {
+        let n = 100 * 1000;
+        let mut a = ThetaSketch::new();
+        let mut b = ThetaSketch::new();
+        let mut union = ThetaUnion::new();
+        for key in 0..n {
+            a.update_u64(key);
+            b.update_u64(key + n / 2);
+        }
+        union.merge(a.as_static());
+        union.merge(b.as_static());
+        let b = b.as_static();
+        a.absorb(&b);
+        let expected = union.sketch().estimate();
+        let estimate = a.estimate();
+        assert!((estimate - expected).abs() / expected < 0.05, "{} vs {}", estimate, expected);
+        assert!((estimate - 1.5 * n as f64).abs() / (1.5 * n as f64) < 0.05, "{}", estimate);
+
+        // exact mode: absorbing a sketch with overlapping values counts each once
+        let mut small = ThetaSketch::new();
+        let mut other = ThetaSketch::new();
+        for key in 0..100 {
+            small.update_u64(key);
+            other.update_u64(key + 50);
+        }
+        small.absorb(&other.as_static());
+        assert_eq!(small.estimate(), 150.0);
+        small.try_absorb(&ThetaSketch::new().as_static()).unwrap();
+        assert_eq!(small.estimate(), 150.0);
+
+        let mut empty = ThetaSketch::new();
+        empty.absorb(&other.as_static());
+        assert_eq!(empty.estimate(), 100.0);
+    }
+
+    #[test]
+    fn into_bytes_roundtrip() {
+        let mut theta = ThetaSketch::new();
+        for key in 0u64..10000 {
+            theta.update_u64(key);
+        }
+        let s = theta.as_static();
+        let bytes = s.serialize_to_vec();
+        let estimate = s.estimate();
+        let owned = s.into_bytes();
+        assert_eq!(owned, bytes);
+        let copy = StaticThetaSketch::deserialize(&owned);
+        assert_eq!(copy.serialize_to_vec(), bytes);
+        assert_eq!(copy.estimate(), estimate);
+    }
+
+    #[test]
+    fn deserialize_owned_buffers() {
+        let mut theta = ThetaSketch::new();
+        theta.update_u64(1);
+        let bytes = theta.as_static().serialize_to_vec();
+        let slice: &[u8] = &bytes;
+        let boxed: Box<[u8]> = bytes.clone().into_boxed_slice();
+        for copy in [
+            StaticThetaSketch::deserialize(slice),
+            StaticThetaSketch::deserialize(boxed),
+            StaticThetaSketch::deserialize(bytes.clone()),
+        ]
+        .iter()
+        {
+            assert_eq!(copy.serialize_to_vec(), bytes);
+        }
+    }
+
+    #[test]
+    fn update_u64_be_portable() {
+        let mut theta = ThetaSketch::new();
+        let mut bytes = ThetaSketch::new();
+        for value in 0u64..4 {
+            theta.update_u64_be(value);
+            bytes.update(&value.to_be_bytes());
+        }
+        let serialized = theta.as_static().serialize_to_vec();
+        assert_eq!(serialized, bytes.as_static().serialize_to_vec());
+        // identical on every platform, unlike with update_u64
+        let reference: &[u8] = &[
+            2, 3, 3, 0, 0, 26, 204, 147, 4, 0, 0, 0, 0, 0, 0, 0, 229, 107, 97, 238, 200, 128, 68,
+            32, 196, 177, 137, 246, 191, 253, 110, 37, 1, 26, 63, 73, 119, 216, 180, 103, 22, 85,
+            60, 156, 197, 34, 185, 107,
+        ];
+        assert_eq!(serialized, reference);
+    }
+
+    #[test]
+    fn update_batch_matches_update() {
+        let keys: Vec<String> = (0..10000).map(|i| format!("key{}", i % 3000)).collect();
+        let keys: Vec<&[u8]> = keys.iter().map(|key| key.as_bytes()).collect();
+        let mut a = ThetaSketch::new();
+        let mut b = ThetaSketch::new();
+        for key in &keys {
+            a.update(key);
+        }
+        b.update_batch(&keys);
+        assert_eq!(
+            a.as_static().serialize().as_ref(),
+            b.as_static().serialize().as_ref()
+        );
+    }
 }